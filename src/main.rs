@@ -3,9 +3,20 @@
 
 mod particle_sim;
 
+pub use crate::particle_sim::backend::{self, BACKEND_NAMES};
+pub use crate::particle_sim::brush::Brush;
+pub use crate::particle_sim::clock::{Clock, DEFAULT_TICK_RATE};
+pub use crate::particle_sim::edge::{EdgeMode, Edges};
+pub use crate::particle_sim::element::{Element, ELEMENTS};
+pub use crate::particle_sim::gravity::Gravity;
+pub use crate::particle_sim::ops::ImportReport;
 pub use crate::particle_sim::particle::{Particle, ParticleVariant};
-pub use crate::particle_sim::world::World;
+pub use crate::particle_sim::tool::{self, Tool};
+pub use crate::particle_sim::world::{World, DEFAULT_HEIGHT, DEFAULT_WIDTH, MAX_SIZE};
 
+use crate::particle_sim::geometry::Point;
+use crate::particle_sim::graphics::{line, rect};
+use crate::particle_sim::loader::load_assets;
 use log::error;
 use pixels::{Error, Pixels, SurfaceTexture};
 use winit::window::Window;
@@ -17,38 +28,209 @@ use winit::{
 };
 use winit_input_helper::WinitInputHelper;
 
-const HEIGHT: u32 = 200;
-const WIDTH: u32 = 200;
+const TITLE: &str = "Powder Toy in Rust";
+/// File written by quick-save and read by quick-load.
+const QUICKSAVE_PATH: &str = "quicksave.ptrs";
 
-const LIGHT_PINK: [u8; 4] = [0xf2, 0x93, 0xb1, 0xff];
-const PINK: [u8; 4] = [0xed, 0x51, 0x81, 0xff];
-const RED: [u8; 4] = [0xe8, 0x2c, 0x45, 0xff];
-const BLUE: [u8; 4] = [0x34, 0x56, 0x9d, 0xff];
-const YELLOW: [u8; 4] = [0xff, 0xf9, 0x75, 0xff];
-const DARK_YELLOW: [u8; 4] = [0xff, 0xea, 0x70, 0xff];
-const ORANGE: [u8; 4] = [0xf8, 0xdb, 0x81, 0xff];
+/// Print the selected element's registry entry.
+fn print_element(element: &Element) {
+    let flags = element.flag_names();
 
-const COLORS: [[u8; 4]; 7] = [LIGHT_PINK, PINK, RED, BLUE, YELLOW, DARK_YELLOW, ORANGE];
+    if flags.is_empty() {
+        println!(
+            "Switched to element: {} ({}) - {}",
+            element.code, element.name, element.description
+        );
+    } else {
+        println!(
+            "Switched to element: {} ({}) - {} [{}]",
+            element.code,
+            element.name,
+            element.description,
+            flags.join(", ")
+        );
+    }
+}
+
+/// Print how much of a save from the original Powder Toy was imported, and what was left out.
+fn print_import_report(path: &str, report: &ImportReport) {
+    println!("Imported {} particles from {}", report.imported, path);
+
+    if !report.unsupported.is_empty() {
+        let elements: Vec<String> = report
+            .unsupported
+            .iter()
+            .map(|(name, count)| format!("{} ({})", name, count))
+            .collect();
+
+        println!("Left out unsupported elements: {}", elements.join(", "));
+    }
+
+    if report.overlapping > 0 {
+        println!("Left out {} overlapping particles", report.overlapping);
+    }
+}
+
+/// The value following a command line flag, if given.
+fn flag_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|arg| arg == flag)
+        .and_then(|i| args.get(i + 1))
+        .map(|value| value.as_str())
+}
+
+/// Parse a world size given as `<width>x<height>`.
+fn parse_size(value: &str) -> Option<(usize, usize)> {
+    let (width, height) = value.split_once('x')?;
+    let size = (width.parse().ok()?, height.parse().ok()?);
+
+    // Leave room for the starting particles, which are placed away from the edges
+    if (9..=MAX_SIZE).contains(&size.0) && (9..=MAX_SIZE).contains(&size.1) {
+        Some(size)
+    } else {
+        None
+    }
+}
+
+/// Draw the selected element into the given cells, or erase them.
+fn apply<I>(world: &mut World, cells: I, variant: ParticleVariant, erase: bool)
+where
+    I: IntoIterator<Item = (i32, i32)>,
+{
+    for (x, y) in cells {
+        if erase {
+            world.remove_particle_at(x, y);
+        } else {
+            world.add_particle(x as f32, y as f32, variant);
+        }
+    }
+}
+
+/// The screen position of a cell, clamped to the world.
+fn point(world: &World, cell: (i32, i32)) -> Point {
+    Point::new(
+        cell.0.clamp(0, world.width() as i32 - 1) as usize,
+        cell.1.clamp(0, world.height() as i32 - 1) as usize,
+    )
+}
 
 fn main() -> Result<(), Error> {
-    let variants: Vec<ParticleVariant> = vec![
-        ParticleVariant::C4,
-        ParticleVariant::DEUT,
-        ParticleVariant::PLUT,
-        ParticleVariant::STNE,
-        ParticleVariant::URAN,
-        ParticleVariant::WOOD,
-    ];
+    let args: Vec<String> = std::env::args().collect();
+
+    // World size in cells, with `--size <width>x<height>`
+    let (width, height) = match flag_value(&args, "--size").map(parse_size) {
+        None => (DEFAULT_WIDTH, DEFAULT_HEIGHT),
+        Some(Some(size)) => size,
+        Some(None) => {
+            eprintln!(
+                "--size expects <width>x<height>, both from 9 to {}, e.g. 612x384",
+                MAX_SIZE
+            );
+            return Ok(());
+        }
+    };
+
+    // Pick the simulation backend with `--backend <name>`, rapier by default
+    let backend_name = flag_value(&args, "--backend").unwrap_or(BACKEND_NAMES[0]);
+
+    if !BACKEND_NAMES.contains(&backend_name) {
+        eprintln!(
+            "Unknown backend {}, expected one of: {}",
+            backend_name,
+            BACKEND_NAMES.join(", ")
+        );
+        return Ok(());
+    }
+
+    // Simulation steps per second at normal speed, with `--tps <rate>`
+    let tick_rate = match flag_value(&args, "--tps").map(str::parse::<f32>) {
+        None => DEFAULT_TICK_RATE,
+        Some(Ok(rate)) if rate > 0.0 => rate,
+        Some(_) => {
+            eprintln!("--tps expects a positive number of steps per second");
+            return Ok(());
+        }
+    };
+
+    // What happens at the edges of the world, with `--edges <mode>` for every side or
+    // `--edges <left>,<right>,<top>,<bottom>`. Without it the top is void and the rest solid.
+    let edges = match flag_value(&args, "--edges").map(Edges::from_names) {
+        None => None,
+        Some(Some(edges)) => Some(edges),
+        Some(None) => {
+            eprintln!(
+                "--edges expects one mode, or four comma-separated modes for the left, right, \
+                 top and bottom edges, each one of: {}",
+                EdgeMode::NAMES.join(", ")
+            );
+            return Ok(());
+        }
+    };
+
+    // Gravity mode with `--gravity <mode>`, or a direction and strength with `--gravity <x>,<y>`
+    let gravity = match flag_value(&args, "--gravity").map(Gravity::from_name) {
+        None => None,
+        Some(Some(gravity)) => Some(gravity),
+        Some(None) => {
+            eprintln!(
+                "--gravity expects one of: {}, or an acceleration in pixels per second squared \
+                 as <x>,<y>",
+                Gravity::NAMES.join(", ")
+            );
+            return Ok(());
+        }
+    };
+
+    // Open a save from the original Powder Toy with `--import <file>`, at the save's size and
+    // with its settings unless overridden
+    let mut world = match flag_value(&args, "--import") {
+        Some(path) => match World::import(path, backend_name) {
+            Ok((world, report)) => {
+                if flag_value(&args, "--size").is_some() {
+                    eprintln!(
+                        "Ignoring --size, imported saves keep their own size of {}x{}",
+                        world.width(),
+                        world.height()
+                    );
+                }
+
+                print_import_report(path, &report);
+                world
+            }
+            Err(e) => {
+                eprintln!("Couldn't import {}: {}", path, e);
+                return Ok(());
+            }
+        },
+        None => World::with_backend(backend::from_name(backend_name, width, height).unwrap()),
+    };
+
+    world.set_tick_rate(tick_rate);
+
+    if let Some(edges) = edges {
+        world.set_edges(edges);
+    }
+
+    if let Some(gravity) = gravity {
+        world.set_gravity(gravity);
+    }
+
+    // Heavy elements pull on everything around them with `--newtonian`, on top of the mode
+    if args.iter().any(|arg| arg == "--newtonian") {
+        world.set_newtonian_gravity(true);
+    }
+
+    let (width, height) = (world.width(), world.height());
 
     let event_loop = EventLoop::new();
     let mut input = WinitInputHelper::new(); // Why is this mutable?
 
     let window: Window = {
-        let size = LogicalSize::new(WIDTH as f64 / 2.0, HEIGHT as f64 / 2.0);
-        let scaled_size = LogicalSize::new(WIDTH as f64 * 2.0, HEIGHT as f64 * 2.0);
+        let size = LogicalSize::new(width as f64 / 2.0, height as f64 / 2.0);
+        let scaled_size = LogicalSize::new(width as f64 * 2.0, height as f64 * 2.0);
 
         WindowBuilder::new()
-            .with_title("Powder Toy in Rust")
+            .with_title(TITLE)
             .with_inner_size(scaled_size)
             .with_min_inner_size(size)
             .build(&event_loop)
@@ -58,17 +240,27 @@ fn main() -> Result<(), Error> {
     let mut pixels = {
         let window_size = window.inner_size();
         let surface_texture = SurfaceTexture::new(window_size.width, window_size.height, &window);
-        Pixels::new(WIDTH, HEIGHT, surface_texture)?
+        Pixels::new(width as u32, height as u32, surface_texture)?
     };
 
-    //pixels.resize_buffer(WIDTH / 2, HEIGHT / 2);
+    //pixels.resize_buffer(width / 2, height / 2);
 
-    let mut world = World::new();
+    let mut clock = Clock::new(world.tick_rate());
     let mut paused = false;
 
-    let mut brush_size: i8 = 1;
+    let mut brush = Brush::new(&load_assets());
+    let mut tool = Tool::Brush;
+    let mut erasing = false;
+
+    // The cell under the mouse, and where the current drag started and whether it erases
+    let mut cursor: Option<(i32, i32)> = None;
+    let mut stroke: Option<((i32, i32), bool)> = None;
+    // Where the brush was last stamped during the current drag
+    let mut last_stamp: Option<(i32, i32)> = None;
+
+    let mut selected_particle_index: usize = ParticleVariant::PLUT as usize;
 
-    let mut selected_particle_index: i8 = 2;
+    let mut title = String::from(TITLE);
 
     event_loop.run(move |event, _, control_flow| {
         if let Event::RedrawRequested(_) = event {
@@ -82,6 +274,30 @@ fn main() -> Result<(), Error> {
             }
 
             world.draw(pixels.get_frame());
+
+            // Preview the line or rectangle being dragged out
+            if let (Some((start, _)), Some(end)) = (stroke, cursor) {
+                let colour = ELEMENTS[selected_particle_index].colour;
+                let (p1, p2) = (point(&world, start), point(&world, end));
+
+                match tool {
+                    Tool::Line => line(pixels.get_frame(), world.width(), &p1, &p2, colour),
+                    Tool::Rectangle => {
+                        let top_left = Point::new(p1.x.min(p2.x), p1.y.min(p2.y));
+                        let bottom_right = Point::new(p1.x.max(p2.x) + 1, p1.y.max(p2.y) + 1);
+
+                        rect(
+                            pixels.get_frame(),
+                            world.width(),
+                            &top_left,
+                            &bottom_right,
+                            colour,
+                        );
+                    }
+                    _ => {}
+                }
+            }
+
             if pixels
                 .render()
                 .map_err(|e| error!("pixels.render() failed: {}", e))
@@ -93,62 +309,194 @@ fn main() -> Result<(), Error> {
         }
 
         if input.update(&event) {
-            if input.mouse_held(1) {
-                let (mouse_x, mouse_y) = input.mouse().expect("Couldn't get mouse position!");
-
-                for x in 0..brush_size {
-                    for y in 0..brush_size {
-                        world.add_particle(
-                            mouse_x / 4.0 + x as f32,
-                            mouse_y / 4.0 + y as f32,
-                            variants[selected_particle_index as usize],
-                        );
+            if let Some(size) = input.window_resized() {
+                pixels.resize_surface(size.width, size.height);
+            }
+
+            // Map the mouse to the cell under it, or the nearest one if it's off the world
+            cursor = input.mouse().map(|position| {
+                let (x, y) = pixels
+                    .window_pos_to_pixel(position)
+                    .unwrap_or_else(|position| pixels.clamp_pixel_pos(position));
+
+                (x as i32, y as i32)
+            });
+
+            // Show what's under the cursor in the title bar
+            let hovered = cursor
+                .and_then(|(x, y)| world.particle_at(x, y))
+                .map_or_else(
+                    || String::from(TITLE),
+                    |particle| {
+                        format!(
+                            "{} - {}, {:.1} C",
+                            TITLE,
+                            particle.element().name,
+                            particle.temperature - 273.15
+                        )
+                    },
+                );
+
+            if hovered != title {
+                window.set_title(&hovered);
+                title = hovered;
+            }
+
+            // Left click draws, or erases in eraser mode, and right click always erases
+            let variant = ELEMENTS[selected_particle_index].variant;
+
+            if let Some(cell) = cursor {
+                if stroke.is_none() && (input.mouse_pressed(0) || input.mouse_pressed(1)) {
+                    let erase = erasing || input.mouse_pressed(1);
+                    stroke = Some((cell, erase));
+
+                    if tool == Tool::Fill {
+                        let region = tool::flood_fill(&world, cell);
+                        apply(&mut world, region, variant, erase);
                     }
                 }
+
+                if let Some((start, erase)) = stroke {
+                    // Stamp the brush all the way from the last mouse sample, so fast drags
+                    // don't leave gaps
+                    if tool == Tool::Brush {
+                        let path = match last_stamp {
+                            Some(last) if last != cell => tool::line(last, cell).split_off(1),
+                            _ => vec![cell],
+                        };
+
+                        let cells = path.into_iter().flat_map(|(x, y)| brush.cells(x, y));
+                        apply(&mut world, cells, variant, erase);
+
+                        last_stamp = Some(cell);
+                    }
+
+                    if input.mouse_released(0) || input.mouse_released(1) {
+                        match tool {
+                            Tool::Line => {
+                                let cells = tool::line(start, cell)
+                                    .into_iter()
+                                    .flat_map(|(x, y)| brush.cells(x, y));
+
+                                apply(&mut world, cells, variant, erase);
+                            }
+                            Tool::Rectangle => {
+                                apply(&mut world, tool::rectangle(start, cell), variant, erase);
+                            }
+                            _ => {}
+                        }
+
+                        stroke = None;
+                    }
+                }
+            }
+
+            if !input.mouse_held(0) && !input.mouse_held(1) {
+                stroke = None;
+            }
+
+            if stroke.is_none() {
+                last_stamp = None;
             }
 
             if input.key_pressed(VirtualKeyCode::Comma) {
                 if selected_particle_index == 0 {
-                    selected_particle_index = 5; // Wrap back around to end of list
+                    selected_particle_index = ELEMENTS.len() - 1; // Wrap back around to end of list
                 } else {
                     selected_particle_index -= 1;
                 }
 
-                let element_string = match variants[selected_particle_index as usize] {
-                    ParticleVariant::C4 => "C4",
-                    ParticleVariant::DEUT => "DEUT",
-                    ParticleVariant::PLUT => "PLUT",
-                    ParticleVariant::STNE => "STNE",
-                    ParticleVariant::URAN => "URAN",
-                    ParticleVariant::WOOD => "WOOD",
-                };
-
-                println!("Switched to element: {}", element_string);
+                print_element(&ELEMENTS[selected_particle_index]);
             } else if input.key_pressed(VirtualKeyCode::Period) {
-                if selected_particle_index == 5 {
+                if selected_particle_index == ELEMENTS.len() - 1 {
                     selected_particle_index = 0; // Wrap back around to start of list
                 } else {
                     selected_particle_index += 1;
                 }
+
+                print_element(&ELEMENTS[selected_particle_index]);
             }
 
             if input.key_pressed(VirtualKeyCode::O) {
-                if brush_size != 1 {
-                    brush_size -= 1;
-                }
+                brush.resize(-1);
 
-                println!("Brush size: {}", brush_size);
+                println!("Brush size: {}", brush.size);
             } else if input.key_pressed(VirtualKeyCode::P) {
-                if brush_size != 20 {
-                    brush_size += 1;
-                }
-                println!("Brush size: {}", brush_size);
+                brush.resize(1);
+
+                println!("Brush size: {}", brush.size);
+            }
+
+            if input.key_pressed(VirtualKeyCode::B) {
+                brush.next_shape();
+
+                println!("Brush shape: {}", brush.name());
+            }
+
+            if input.key_pressed(VirtualKeyCode::T) {
+                tool = tool.next();
+
+                println!("Tool: {:?}", tool);
+            }
+
+            if input.key_pressed(VirtualKeyCode::E) {
+                erasing = !erasing;
+
+                println!("Eraser: {}", if erasing { "on" } else { "off" });
             }
 
             if input.key_pressed(VirtualKeyCode::R) {
                 world.clear_particles();
             }
 
+            // Cycle every edge to the mode after the bottom edge's
+            if input.key_pressed(VirtualKeyCode::X) {
+                let mode = world.edges().bottom.next();
+                world.set_edges(Edges::all(mode));
+
+                println!("Edges: {}", mode.name());
+            }
+
+            if input.key_pressed(VirtualKeyCode::F5) {
+                match world.save(QUICKSAVE_PATH) {
+                    Ok(()) => println!("Saved to {}", QUICKSAVE_PATH),
+                    Err(e) => eprintln!("Couldn't save to {}: {}", QUICKSAVE_PATH, e),
+                }
+            }
+
+            // The save may be of a different size and tick rate, so the pixel buffer and clock
+            // follow the world
+            if input.key_pressed(VirtualKeyCode::F9) {
+                match World::load(QUICKSAVE_PATH) {
+                    Ok(loaded) => {
+                        world = loaded;
+                        pixels.resize_buffer(world.width() as u32, world.height() as u32);
+                        clock.set_tick_rate(world.tick_rate());
+                        stroke = None;
+
+                        println!("Loaded {}", QUICKSAVE_PATH);
+                    }
+                    Err(e) => eprintln!("Couldn't load {}: {}", QUICKSAVE_PATH, e),
+                }
+            }
+
+            if input.key_pressed(VirtualKeyCode::G) {
+                world.set_gravity(world.gravity().next());
+
+                println!("Gravity: {}", world.gravity().name());
+            }
+
+            if input.key_pressed(VirtualKeyCode::N) {
+                world.set_newtonian_gravity(!world.newtonian_gravity());
+
+                let state = if world.newtonian_gravity() {
+                    "on"
+                } else {
+                    "off"
+                };
+                println!("Newtonian gravity: {}", state);
+            }
+
             if input.key_pressed(VirtualKeyCode::Escape) {
                 *control_flow = ControlFlow::Exit;
                 return;
@@ -156,9 +504,41 @@ fn main() -> Result<(), Error> {
 
             if input.key_pressed(winit::event::VirtualKeyCode::Space) {
                 paused = !paused;
+
+                println!("{}", if paused { "Paused" } else { "Resumed" });
+            }
+
+            // Advance exactly one step, pausing first if running
+            if input.key_pressed(VirtualKeyCode::F) {
+                if paused {
+                    world.update();
+                } else {
+                    paused = true;
+
+                    println!("Paused");
+                }
             }
+
+            if input.key_pressed(VirtualKeyCode::LBracket) {
+                clock.slower();
+
+                println!("Speed: {}x", clock.speed());
+            } else if input.key_pressed(VirtualKeyCode::RBracket) {
+                clock.faster();
+
+                println!("Speed: {}x", clock.speed());
+            }
+
+            // Run however many fixed steps are due, regardless of the frame rate
+            if paused {
+                clock.reset();
+            } else {
+                for _ in 0..clock.steps() {
+                    world.update();
+                }
+            }
+
+            window.request_redraw();
         }
-        world.update();
-        window.request_redraw();
     });
 }
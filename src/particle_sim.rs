@@ -1,5 +1,26 @@
+pub mod air;
+pub mod backend;
+pub mod brush;
+pub mod clock;
+pub mod combustion;
+pub mod edge;
+pub mod element;
+pub mod explosive;
+pub mod fluid;
+pub mod fusion;
 pub mod geometry;
 pub mod graphics;
+pub mod gravity;
+pub mod grid;
+pub mod heat;
 pub mod loader;
+pub mod occupancy;
+pub mod ops;
 pub mod particle;
+pub mod phase;
+pub mod radioactivity;
+pub mod rapier;
+pub mod reaction;
+pub mod save;
+pub mod tool;
 pub mod world;
@@ -0,0 +1,214 @@
+//! Coarse grid of air cells laid over the world.
+
+use super::heat::ROOM_TEMPERATURE;
+
+/// Width and height of a single air cell, in world pixels.
+pub const CELL_SIZE: usize = 4;
+
+/// Fraction of the difference to the neighbouring cells' average heat mixed in per step.
+const HEAT_DIFFUSION_RATE: f32 = 0.1;
+/// Fraction of each cell's pressure kept per step.
+const PRESSURE_LOSS: f32 = 0.995;
+/// Fraction of each cell's velocity kept per step.
+const VELOCITY_LOSS: f32 = 0.99;
+/// How strongly converging air raises pressure.
+const PRESSURE_STEP: f32 = 0.3;
+/// How strongly a pressure difference accelerates the air between two cells.
+const VELOCITY_STEP: f32 = 0.4;
+/// Cells travelled per step per unit of air velocity by heat carried along with the flow.
+const ADVECTION_STEP: f32 = 0.1;
+/// Upward acceleration of air per Kelvin above room temperature.
+const BUOYANCY: f32 = 0.0002;
+/// Largest pressure or velocity magnitude a cell can hold.
+const MAX_FLOW: f32 = 256.0;
+
+/// The ambient air layer, stored row-major at `CELL_SIZE` resolution.
+pub struct AirGrid {
+    width: usize,
+    height: usize,
+    /// Ambient temperature of each cell, in Kelvin.
+    pub heat: Vec<f32>,
+    /// Pressure of each cell relative to the ambient air.
+    pub pressure: Vec<f32>,
+    /// Horizontal air velocity of each cell.
+    pub velocity_x: Vec<f32>,
+    /// Vertical air velocity of each cell.
+    pub velocity_y: Vec<f32>,
+}
+
+impl AirGrid {
+    /// Create an air grid covering a world of the given size in pixels.
+    pub fn new(world_width: usize, world_height: usize) -> Self {
+        let width = world_width.div_ceil(CELL_SIZE);
+        let height = world_height.div_ceil(CELL_SIZE);
+
+        AirGrid {
+            width,
+            height,
+            heat: vec![ROOM_TEMPERATURE; width * height],
+            pressure: vec![0.0; width * height],
+            velocity_x: vec![0.0; width * height],
+            velocity_y: vec![0.0; width * height],
+        }
+    }
+
+    /// Index of the cell containing the given world position, if it lies inside the grid.
+    pub fn cell_index(&self, x: f32, y: f32) -> Option<usize> {
+        if x < 0.0 || y < 0.0 {
+            return None;
+        }
+
+        let cx = x as usize / CELL_SIZE;
+        let cy = y as usize / CELL_SIZE;
+
+        if cx >= self.width || cy >= self.height {
+            return None;
+        }
+
+        Some(cy * self.width + cx)
+    }
+
+    /// Diffuse ambient heat between neighbouring cells.
+    ///
+    /// Cells outside the grid are held at room temperature, so the world slowly cools towards
+    /// its edges.
+    pub fn update_heat(&mut self) {
+        let previous = self.heat.clone();
+
+        let heat_at = |x: isize, y: isize| {
+            if x < 0 || y < 0 || x >= self.width as isize || y >= self.height as isize {
+                ROOM_TEMPERATURE
+            } else {
+                previous[y as usize * self.width + x as usize]
+            }
+        };
+
+        for y in 0..self.height as isize {
+            for x in 0..self.width as isize {
+                let average =
+                    (heat_at(x - 1, y) + heat_at(x + 1, y) + heat_at(x, y - 1) + heat_at(x, y + 1))
+                        / 4.0;
+
+                let i = y as usize * self.width + x as usize;
+                self.heat[i] += (average - previous[i]) * HEAT_DIFFUSION_RATE;
+            }
+        }
+    }
+
+    /// Advance the pressure and velocity fields by one step.
+    ///
+    /// Air flows from high to low pressure, pressure builds up where air converges, hot air
+    /// rises and heat is carried along with the flow. Air outside the grid is still and at
+    /// ambient pressure, so the edges of the world are open.
+    pub fn update_flow(&mut self) {
+        let (width, height) = (self.width as isize, self.height as isize);
+        let index = |x: isize, y: isize| {
+            if x < 0 || y < 0 || x >= width || y >= height {
+                None
+            } else {
+                Some(y as usize * self.width + x as usize)
+            }
+        };
+
+        // Pressure rises where air converges and falls where it spreads out
+        let (velocity_x, velocity_y) = (self.velocity_x.clone(), self.velocity_y.clone());
+        let sample = |field: &[f32], x: isize, y: isize| index(x, y).map_or(0.0, |i| field[i]);
+
+        for y in 0..height {
+            for x in 0..width {
+                let i = y as usize * self.width + x as usize;
+                let divergence = sample(&velocity_x, x - 1, y) - velocity_x[i]
+                    + sample(&velocity_y, x, y - 1)
+                    - velocity_y[i];
+
+                self.pressure[i] = (self.pressure[i] * PRESSURE_LOSS + divergence * PRESSURE_STEP)
+                    .clamp(-MAX_FLOW, MAX_FLOW);
+            }
+        }
+
+        // Air accelerates down the pressure gradient, and upwards where it is hot
+        for y in 0..height {
+            for x in 0..width {
+                let i = y as usize * self.width + x as usize;
+                let dx = self.pressure[i] - sample(&self.pressure, x + 1, y);
+                let dy = self.pressure[i] - sample(&self.pressure, x, y + 1);
+                let buoyancy = (self.heat[i] - ROOM_TEMPERATURE) * BUOYANCY;
+
+                self.velocity_x[i] = (self.velocity_x[i] * VELOCITY_LOSS + dx * VELOCITY_STEP)
+                    .clamp(-MAX_FLOW, MAX_FLOW);
+                self.velocity_y[i] = (self.velocity_y[i] * VELOCITY_LOSS + dy * VELOCITY_STEP
+                    - buoyancy)
+                    .clamp(-MAX_FLOW, MAX_FLOW);
+            }
+        }
+
+        // Carry heat along with the flow
+        let heat = self.heat.clone();
+
+        for y in 0..height {
+            for x in 0..width {
+                let i = y as usize * self.width + x as usize;
+                let source_x = x as f32 - self.velocity_x[i] * ADVECTION_STEP;
+                let source_y = y as f32 - self.velocity_y[i] * ADVECTION_STEP;
+
+                self.heat[i] = self.interpolate(&heat, source_x, source_y, ROOM_TEMPERATURE);
+            }
+        }
+    }
+
+    /// Bilinearly sample a field at a fractional cell position, using `outside` beyond the grid.
+    fn interpolate(&self, field: &[f32], x: f32, y: f32, outside: f32) -> f32 {
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as isize, y0 as isize);
+
+        let at = |x: isize, y: isize| {
+            if x < 0 || y < 0 || x >= self.width as isize || y >= self.height as isize {
+                outside
+            } else {
+                field[y as usize * self.width + x as usize]
+            }
+        };
+
+        let top = at(x0, y0) * (1.0 - fx) + at(x0 + 1, y0) * fx;
+        let bottom = at(x0, y0 + 1) * (1.0 - fx) + at(x0 + 1, y0 + 1) * fx;
+
+        top * (1.0 - fy) + bottom * fy
+    }
+
+    /// Add heat to the cell containing the given world position.
+    pub fn add_heat(&mut self, x: f32, y: f32, amount: f32) {
+        if let Some(cell) = self.cell_index(x, y) {
+            self.heat[cell] += amount;
+        }
+    }
+
+    /// Pressure of the cell containing the given world position, or 0 outside the grid.
+    pub fn pressure_at(&self, x: f32, y: f32) -> f32 {
+        self.cell_index(x, y)
+            .map_or(0.0, |cell| self.pressure[cell])
+    }
+
+    /// Air velocity of the cell containing the given world position, or still air outside the
+    /// grid.
+    pub fn velocity_at(&self, x: f32, y: f32) -> (f32, f32) {
+        self.cell_index(x, y).map_or((0.0, 0.0), |cell| {
+            (self.velocity_x[cell], self.velocity_y[cell])
+        })
+    }
+
+    /// Set the air velocity of the cell containing the given world position.
+    pub fn set_velocity(&mut self, x: f32, y: f32, velocity: (f32, f32)) {
+        if let Some(cell) = self.cell_index(x, y) {
+            self.velocity_x[cell] = velocity.0;
+            self.velocity_y[cell] = velocity.1;
+        }
+    }
+
+    /// Add pressure to the cell containing the given world position.
+    pub fn add_pressure(&mut self, x: f32, y: f32, amount: f32) {
+        if let Some(cell) = self.cell_index(x, y) {
+            self.pressure[cell] += amount;
+        }
+    }
+}
@@ -0,0 +1,99 @@
+//! The engines that move particles around, behind a common interface so `World` doesn't care
+//! which one it runs on.
+
+use rapier2d::prelude::*;
+
+use super::{
+    edge::Edges, gravity::GravityField, grid::GridBackend, particle::Particle,
+    rapier::RapierBackend,
+};
+
+/// Names accepted by `from_name`, default first.
+pub const BACKEND_NAMES: [&str; 2] = ["rapier", "grid"];
+
+/// A simulation backend owns the particles and moves them.
+///
+/// Particles are addressed by their index into `particles()`. Indices stay valid until a particle
+/// is removed, which moves the last particle into the freed slot.
+pub trait Backend {
+    /// The name `from_name` creates this backend for.
+    fn name(&self) -> &'static str;
+
+    /// Width and height of the world the backend was created for, in cells.
+    fn size(&self) -> (usize, usize);
+
+    /// Move every particle by one step of `dt` seconds, pulled by the given gravity.
+    fn step(&mut self, dt: f32, gravity: &GravityField);
+
+    /// Add a particle, returning whether there was room for it.
+    fn add(&mut self, particle: Particle) -> bool;
+
+    /// Remove the particle at `index`, moving the last particle into its place.
+    ///
+    /// Anything the backend keeps for the particle besides its entry in `particles()` must be
+    /// freed too.
+    fn remove(&mut self, index: usize) -> Particle;
+
+    /// Replace the particle at `index` with another one in the same place.
+    fn replace(&mut self, index: usize, particle: Particle);
+
+    fn particles(&self) -> &[Particle];
+
+    fn particles_mut(&mut self) -> &mut [Particle];
+
+    /// Change what happens at the edges of the world.
+    ///
+    /// `World` deletes or wraps particles that end up outside the world after a step, so a
+    /// backend only needs to keep particles in at solid edges and let them out at the others.
+    fn set_edges(&mut self, edges: Edges);
+
+    /// Whether the backend layers and spreads fluids itself, so `World` shouldn't.
+    fn moves_fluids(&self) -> bool {
+        false
+    }
+
+    /// Apply an impulse to a particle, changing its velocity by the impulse over its mass.
+    fn apply_impulse(&mut self, index: usize, impulse: Vector<Real>) {
+        let particle = &mut self.particles_mut()[index];
+        let density = particle.element().density;
+
+        particle.vx += impulse.x / density;
+        particle.vy += impulse.y / density;
+    }
+
+    /// Set a particle's velocity, in pixels per second.
+    fn set_velocity(&mut self, index: usize, velocity: Vector<Real>) {
+        let particle = &mut self.particles_mut()[index];
+
+        particle.vx = velocity.x;
+        particle.vy = velocity.y;
+    }
+
+    /// Move a particle, keeping its velocity.
+    fn set_position(&mut self, index: usize, x: f32, y: f32) {
+        let particle = &mut self.particles_mut()[index];
+
+        particle.x = x;
+        particle.y = y;
+    }
+
+    /// Swap the positions of two particles.
+    fn swap(&mut self, i: usize, j: usize) {
+        let particles = self.particles_mut();
+        let (x, y) = (particles[i].x, particles[i].y);
+
+        particles[i].x = particles[j].x;
+        particles[i].y = particles[j].y;
+        particles[j].x = x;
+        particles[j].y = y;
+    }
+}
+
+/// Create the backend with the given name for a world of the given size in pixels.
+pub fn from_name(name: &str, width: usize, height: usize) -> Option<Box<dyn Backend>> {
+    match name {
+        "rapier" => Some(Box::new(RapierBackend::new(width, height))),
+        "grid" => Some(Box::new(GridBackend::new(width, height))),
+        _ => None,
+    }
+}
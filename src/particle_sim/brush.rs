@@ -0,0 +1,119 @@
+//! The brush used to draw and erase particles.
+
+use super::loader::Assets;
+
+/// Largest brush size.
+pub const MAX_SIZE: i32 = 20;
+
+/// A bitmap brush: its name, width, height and which of its pixels it paints.
+type Bitmap = (&'static str, usize, usize, Vec<bool>);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Shape {
+    Circle,
+    Square,
+    /// An upward-pointing triangle.
+    Triangle,
+    /// One of the bitmap brushes, by index.
+    Bitmap(usize),
+}
+
+/// The cells painted around the cursor.
+///
+/// Geometric shapes reach `size - 1` cells from the centre in each direction, so size 1 paints a
+/// single cell. Bitmap brushes are always drawn at their own size.
+pub struct Brush {
+    pub shape: Shape,
+    pub size: i32,
+    bitmaps: Vec<Bitmap>,
+}
+
+impl Brush {
+    /// Create a single-cell circle brush, with the bitmap brushes from the assets to pick from.
+    pub(crate) fn new(assets: &Assets) -> Self {
+        let bitmaps = assets
+            .brushes()
+            .iter()
+            .map(|(name, (width, height, pixels))| {
+                // Any pixel that isn't black is painted
+                let mask = pixels
+                    .chunks_exact(4)
+                    .map(|pixel| pixel[..3].iter().any(|&channel| channel > 0))
+                    .collect();
+
+                (*name, *width, *height, mask)
+            })
+            .collect();
+
+        Brush {
+            shape: Shape::Circle,
+            size: 1,
+            bitmaps,
+        }
+    }
+
+    /// Switch to the next shape, wrapping around after the last bitmap brush.
+    pub fn next_shape(&mut self) {
+        self.shape = match self.shape {
+            Shape::Circle => Shape::Square,
+            Shape::Square => Shape::Triangle,
+            Shape::Triangle if !self.bitmaps.is_empty() => Shape::Bitmap(0),
+            Shape::Bitmap(i) if i + 1 < self.bitmaps.len() => Shape::Bitmap(i + 1),
+            _ => Shape::Circle,
+        };
+    }
+
+    pub fn resize(&mut self, change: i32) {
+        self.size = (self.size + change).clamp(1, MAX_SIZE);
+    }
+
+    /// Name of the current shape, for display.
+    pub fn name(&self) -> &'static str {
+        match self.shape {
+            Shape::Circle => "Circle",
+            Shape::Square => "Square",
+            Shape::Triangle => "Triangle",
+            Shape::Bitmap(i) => self.bitmaps[i].0,
+        }
+    }
+
+    /// The cells painted with the brush centred on the given cell.
+    pub fn cells(&self, x: i32, y: i32) -> Vec<(i32, i32)> {
+        let reach = self.size - 1;
+        let mut cells = Vec::new();
+
+        if let Shape::Bitmap(i) = self.shape {
+            let (_, width, height, mask) = &self.bitmaps[i];
+            let (left, top) = (x - *width as i32 / 2, y - *height as i32 / 2);
+
+            for row in 0..*height {
+                for column in 0..*width {
+                    if mask[row * width + column] {
+                        cells.push((left + column as i32, top + row as i32));
+                    }
+                }
+            }
+
+            return cells;
+        }
+
+        for dy in -reach..=reach {
+            for dx in -reach..=reach {
+                let inside = match self.shape {
+                    // Allowing an extra half cell of radius keeps the edges from looking spiky
+                    Shape::Circle => dx * dx + dy * dy <= reach * reach + reach,
+                    Shape::Square => true,
+                    // Widens by a cell on each side every two rows down from the apex
+                    Shape::Triangle => 2 * dx.abs() <= dy + reach,
+                    Shape::Bitmap(_) => false,
+                };
+
+                if inside {
+                    cells.push((x + dx, y + dy));
+                }
+            }
+        }
+
+        cells
+    }
+}
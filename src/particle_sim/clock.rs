@@ -0,0 +1,80 @@
+//! Fixed-timestep clock deciding how many simulation steps to run each frame.
+
+use std::time::Instant;
+
+/// Default number of simulation steps per second at normal speed.
+pub const DEFAULT_TICK_RATE: f32 = 45.0;
+/// Slowest and fastest speed multipliers.
+pub const MIN_SPEED: f32 = 0.25;
+pub const MAX_SPEED: f32 = 4.0;
+/// Most steps run in a single frame. Time beyond this is dropped, so a slow frame can't make
+/// the next one slower still.
+const MAX_STEPS_PER_FRAME: u32 = 16;
+
+/// Accumulates real time and hands it out as whole simulation steps, so the simulation runs at
+/// the same speed however often frames and input events arrive.
+pub struct Clock {
+    tick_rate: f32,
+    /// Multiplier on how much simulated time passes per second of real time.
+    speed: f32,
+    /// Real time waiting to be simulated, in seconds scaled by `speed`.
+    accumulator: f32,
+    last: Instant,
+}
+
+impl Clock {
+    pub fn new(tick_rate: f32) -> Self {
+        Clock {
+            tick_rate,
+            speed: 1.0,
+            accumulator: 0.0,
+            last: Instant::now(),
+        }
+    }
+
+    /// Change how many steps make up a second at normal speed, such as after loading a world
+    /// saved with a different rate.
+    pub fn set_tick_rate(&mut self, tick_rate: f32) {
+        self.tick_rate = tick_rate;
+        self.reset();
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    /// Double or halve the speed, within `MIN_SPEED` and `MAX_SPEED`.
+    pub fn faster(&mut self) {
+        self.speed = (self.speed * 2.0).min(MAX_SPEED);
+    }
+
+    pub fn slower(&mut self) {
+        self.speed = (self.speed / 2.0).max(MIN_SPEED);
+    }
+
+    /// Number of steps due since the last call.
+    pub fn steps(&mut self) -> u32 {
+        let now = Instant::now();
+
+        self.accumulator += (now - self.last).as_secs_f32() * self.speed;
+        self.last = now;
+
+        let step = 1.0 / self.tick_rate;
+        let steps = (self.accumulator / step) as u32;
+
+        if steps > MAX_STEPS_PER_FRAME {
+            self.accumulator = 0.0;
+            return MAX_STEPS_PER_FRAME;
+        }
+
+        self.accumulator -= steps as f32 * step;
+
+        steps
+    }
+
+    /// Forget the time passed since the last call, so nothing is caught up after a pause.
+    pub fn reset(&mut self) {
+        self.accumulator = 0.0;
+        self.last = Instant::now();
+    }
+}
@@ -0,0 +1,97 @@
+//! Burning of flammable elements.
+
+use rand::Rng;
+
+use super::{
+    element::PROP_IGNITER,
+    occupancy::{Occupancy, NEIGHBOURS},
+    particle::{Particle, ParticleVariant},
+    reaction::Reaction,
+};
+
+/// Temperature gained by a burning particle each step, in Kelvin.
+const BURN_HEAT: f32 = 20.0;
+
+/// Cells fire can spread into from a burning particle, weighted upwards.
+const SPREAD: [(i32, i32); 6] = [(-1, -1), (0, -1), (0, -1), (1, -1), (-1, 0), (1, 0)];
+
+/// Ignite flammable particles that are hot enough or touching a flame, and burn the ones
+/// already alight.
+///
+/// Burning particles heat up, spawn fire into empty neighbouring cells and eventually burn
+/// out into their residue.
+pub fn burn(particles: &mut [Particle], cells: &Occupancy, reactions: &mut Vec<Reaction>) {
+    let mut rng = rand::thread_rng();
+
+    // Flames light their flammable neighbours with a chance based on their flammability
+    let mut lit = Vec::new();
+
+    for particle in particles.iter() {
+        if !particle.burning && !particle.element().has_flag(PROP_IGNITER) {
+            continue;
+        }
+
+        let (x, y) = particle.cell();
+
+        for (dx, dy) in NEIGHBOURS {
+            if let Some(j) = cells.get(x + dx, y + dy) {
+                if let Some(combustion) = &particles[j].element().combustion {
+                    if rng.gen_range(0..100) < combustion.flammability {
+                        lit.push(j);
+                    }
+                }
+            }
+        }
+    }
+
+    for j in lit {
+        let combustion = particles[j].element().combustion.as_ref().unwrap();
+        let particle = &mut particles[j];
+
+        if !particle.burning {
+            particle.burning = true;
+            particle.life = combustion.burn_time;
+        }
+    }
+
+    for (i, particle) in particles.iter_mut().enumerate() {
+        let combustion = match &particle.element().combustion {
+            Some(combustion) => combustion,
+            None => continue,
+        };
+
+        if !particle.burning {
+            if particle.temperature > combustion.ignition_temperature {
+                particle.burning = true;
+                particle.life = combustion.burn_time;
+            }
+
+            continue;
+        }
+
+        particle.temperature += BURN_HEAT;
+        particle.life -= 1;
+
+        if particle.life <= 0 {
+            reactions.push(match combustion.residue {
+                Some(variant) => Reaction::Change(i, variant),
+                None => Reaction::Remove(i),
+            });
+
+            continue;
+        }
+
+        if rng.gen_range(0..100) < combustion.flammability {
+            let (x, y) = particle.cell();
+            let (dx, dy) = SPREAD[rng.gen_range(0..SPREAD.len())];
+
+            if !cells.is_occupied(x + dx, y + dy) {
+                reactions.push(Reaction::Spawn(
+                    (x + dx) as f32,
+                    (y + dy) as f32,
+                    ParticleVariant::FIRE,
+                ));
+            }
+        }
+    }
+}
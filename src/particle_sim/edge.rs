@@ -0,0 +1,117 @@
+//! What happens to particles at the edges of the world.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EdgeMode {
+    /// A wall particles can't pass.
+    Solid,
+    /// Particles leaving through the edge are deleted.
+    Void,
+    /// Particles leaving through the edge come back in through the opposite one.
+    Wrap,
+}
+
+impl EdgeMode {
+    /// Names accepted by `from_name`, in the order `next` cycles through them.
+    pub const NAMES: [&'static str; 3] = ["solid", "void", "wrap"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "solid" => Some(EdgeMode::Solid),
+            "void" => Some(EdgeMode::Void),
+            "wrap" => Some(EdgeMode::Wrap),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            EdgeMode::Solid => Self::NAMES[0],
+            EdgeMode::Void => Self::NAMES[1],
+            EdgeMode::Wrap => Self::NAMES[2],
+        }
+    }
+
+    /// The next mode, wrapping around after the last.
+    pub fn next(self) -> Self {
+        match self {
+            EdgeMode::Solid => EdgeMode::Void,
+            EdgeMode::Void => EdgeMode::Wrap,
+            EdgeMode::Wrap => EdgeMode::Solid,
+        }
+    }
+}
+
+/// The mode of each edge of the world.
+///
+/// Wrapping only makes sense if the opposite edge wraps too, otherwise particles coming back in
+/// may end up stuck in a wall or deleted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Edges {
+    pub left: EdgeMode,
+    pub right: EdgeMode,
+    pub top: EdgeMode,
+    pub bottom: EdgeMode,
+}
+
+impl Edges {
+    /// The same mode on every side.
+    pub const fn all(mode: EdgeMode) -> Self {
+        Edges {
+            left: mode,
+            right: mode,
+            top: mode,
+            bottom: mode,
+        }
+    }
+
+    /// Parse either a single mode for every side, or four comma-separated modes for the left,
+    /// right, top and bottom edges.
+    pub fn from_names(names: &str) -> Option<Self> {
+        let modes = names
+            .split(',')
+            .map(EdgeMode::from_name)
+            .collect::<Option<Vec<_>>>()?;
+
+        match modes[..] {
+            [mode] => Some(Self::all(mode)),
+            [left, right, top, bottom] => Some(Edges {
+                left,
+                right,
+                top,
+                bottom,
+            }),
+            _ => None,
+        }
+    }
+
+    /// The mode of the edge a position is beyond horizontally and vertically, if any.
+    pub fn beyond(&self, x: f32, y: f32, width: f32, height: f32) -> [Option<EdgeMode>; 2] {
+        let horizontal = if x < 0.0 {
+            Some(self.left)
+        } else if x >= width {
+            Some(self.right)
+        } else {
+            None
+        };
+
+        let vertical = if y < 0.0 {
+            Some(self.top)
+        } else if y >= height {
+            Some(self.bottom)
+        } else {
+            None
+        };
+
+        [horizontal, vertical]
+    }
+}
+
+impl Default for Edges {
+    fn default() -> Self {
+        // Walled in on the left, right and bottom, with the top left open
+        Edges {
+            top: EdgeMode::Void,
+            ..Self::all(EdgeMode::Solid)
+        }
+    }
+}
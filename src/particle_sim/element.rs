@@ -0,0 +1,661 @@
+//! Element registry.
+//!
+//! Every per-element property lives in a single entry of the `ELEMENTS` table, so adding an
+//! element means adding a `ParticleVariant` and one table entry.
+
+use rand::Rng;
+
+use super::{
+    heat::{MAX_TEMPERATURE, ROOM_TEMPERATURE},
+    particle::{ParticleVariant, PhysicsType},
+};
+
+/// The element sets flammable neighbours alight and sets off explosives.
+pub const PROP_IGNITER: u32 = 1 << 0;
+/// The element is destroyed by explosions.
+pub const PROP_BREAKABLE: u32 = 1 << 1;
+/// Pressure packs several particles of the element into one.
+pub const PROP_COMPRESSIBLE: u32 = 1 << 2;
+
+/// Human readable names for each `PROP_*` flag, used by the UI.
+const FLAG_NAMES: [(u32, &str); 3] = [
+    (PROP_IGNITER, "igniter"),
+    (PROP_BREAKABLE, "breakable"),
+    (PROP_COMPRESSIBLE, "compressible"),
+];
+
+/// A phase change into another element once a temperature threshold is crossed.
+#[derive(Debug)]
+pub struct Transition {
+    /// Threshold temperature in Kelvin.
+    pub temperature: f32,
+    pub into: ParticleVariant,
+}
+
+/// How a flammable element burns.
+#[derive(Debug)]
+pub struct Combustion {
+    /// Temperature above which the element catches fire, in Kelvin.
+    pub ignition_temperature: f32,
+    /// Chance out of 100 each step that a burning particle spawns fire next to itself.
+    pub flammability: u8,
+    /// Number of steps the element burns for.
+    pub burn_time: i32,
+    /// What is left once the particle has burnt out, or `None` if nothing is.
+    pub residue: Option<ParticleVariant>,
+}
+
+/// How an explosive element detonates.
+#[derive(Debug)]
+pub struct Explosion {
+    /// Temperature above which the element detonates on its own, in Kelvin.
+    pub detonation_temperature: f32,
+    /// Radius of the blast, in pixels.
+    pub radius: f32,
+    /// Impulse applied to dynamic bodies at the centre of the blast.
+    pub impulse: f32,
+    /// Temperature added to particles at the centre of the blast, in Kelvin.
+    pub heat: f32,
+    /// Pressure added to the air cell the blast starts in.
+    pub pressure: f32,
+}
+
+/// How a radioactive element emits and reacts to neutrons.
+#[derive(Debug)]
+pub struct Radioactivity {
+    /// Chance out of 10000 each step of spontaneously emitting a neutron.
+    pub emission_chance: u16,
+    /// Chance out of 100 that an absorbed neutron splits the particle's nucleus.
+    pub fission_chance: u8,
+    /// Neutrons released by each fission, multiplied by the particles packed into it.
+    pub fission_neutrons: u8,
+    /// Whether fission uses up the particle.
+    pub consumed: bool,
+    /// Heat released into the particle by each absorbed neutron, in Kelvin.
+    pub heat: f32,
+}
+
+/// How an element fuses under extreme temperature and pressure.
+#[derive(Debug)]
+pub struct Fusion {
+    /// Temperature above which the element can fuse, in Kelvin.
+    pub temperature: f32,
+    /// Air pressure above which the element can fuse.
+    pub pressure: f32,
+    pub product: ParticleVariant,
+    /// Heat released into the product by each fused particle, in Kelvin.
+    pub heat: f32,
+}
+
+/// Static properties shared by every particle of one element.
+#[derive(Debug)]
+pub struct Element {
+    pub variant: ParticleVariant,
+    pub name: &'static str,
+    /// Short upper-case code shown in the UI, as in the original Powder Toy.
+    pub code: &'static str,
+    pub description: &'static str,
+    pub colour: [u8; 4],
+    /// Maximum brightness offset applied to `colour` for each new particle, 0 for flat colour.
+    pub colour_variation: u8,
+    pub physics_type: PhysicsType,
+    /// Density relative to water, used as the mass of a single particle.
+    pub density: f32,
+    /// Temperature of newly placed particles, in Kelvin.
+    pub default_temperature: f32,
+    /// How readily heat flows through the element, from 0 (insulator) to 255.
+    pub heat_conductivity: u8,
+    /// Transition taken when the particle cools below the threshold.
+    pub low_transition: Option<Transition>,
+    /// Transition taken when the particle heats above the threshold.
+    pub high_transition: Option<Transition>,
+    /// Number of steps a new particle lives for, or 0 if it never decays.
+    pub life: i32,
+    /// What the particle turns into once its life runs out, or `None` to remove it.
+    pub decays_into: Option<ParticleVariant>,
+    pub combustion: Option<Combustion>,
+    pub explosion: Option<Explosion>,
+    /// Chance out of 100 each step that a neutron passing through the element is absorbed.
+    pub neutron_absorption: u8,
+    /// Chance out of 100 each step that a neutron passing through the element is deflected.
+    pub neutron_scattering: u8,
+    pub radioactivity: Option<Radioactivity>,
+    pub fusion: Option<Fusion>,
+    /// Air velocity the element holds its air cell at.
+    pub fan: Option<(f32, f32)>,
+    /// Bitwise OR of `PROP_*` flags, for behaviour not already implied by the fields above.
+    pub flags: u32,
+}
+
+/// The element registry, indexed by `ParticleVariant`.
+pub static ELEMENTS: [Element; 17] = [
+    Element {
+        variant: ParticleVariant::WOOD,
+        name: "Wood",
+        code: "WOOD",
+        description: "Solid. Flammable.",
+        colour: [0xc0, 0xa0, 0x40, 0xff],
+        colour_variation: 10,
+        physics_type: PhysicsType::STATIC,
+        density: 0.7,
+        default_temperature: ROOM_TEMPERATURE,
+        heat_conductivity: 164,
+        low_transition: None,
+        high_transition: None,
+        life: 0,
+        decays_into: None,
+        combustion: Some(Combustion {
+            ignition_temperature: 873.15,
+            flammability: 20,
+            burn_time: 120,
+            residue: Some(ParticleVariant::ASH),
+        }),
+        explosion: None,
+        neutron_absorption: 5,
+        neutron_scattering: 10,
+        radioactivity: None,
+        fusion: None,
+        fan: None,
+        flags: PROP_BREAKABLE,
+    },
+    Element {
+        variant: ParticleVariant::STNE,
+        name: "Stone",
+        code: "STNE",
+        description: "Heavy particles. Meltable.",
+        colour: [0xa0, 0xa0, 0xa0, 0xff],
+        colour_variation: 16,
+        physics_type: PhysicsType::STATIC,
+        density: 2.6,
+        default_temperature: ROOM_TEMPERATURE,
+        heat_conductivity: 150,
+        low_transition: None,
+        high_transition: Some(Transition {
+            temperature: 983.15,
+            into: ParticleVariant::LAVA,
+        }),
+        life: 0,
+        decays_into: None,
+        combustion: None,
+        explosion: None,
+        neutron_absorption: 10,
+        neutron_scattering: 20,
+        radioactivity: None,
+        fusion: None,
+        fan: None,
+        flags: PROP_BREAKABLE,
+    },
+    Element {
+        variant: ParticleVariant::URAN,
+        name: "Uranium",
+        code: "URAN",
+        description:
+            "Heavy particles. Heats up when it absorbs neutrons, sometimes releasing more.",
+        colour: [0x70, 0x70, 0x20, 0xff],
+        colour_variation: 8,
+        physics_type: PhysicsType::DYNAMIC,
+        density: 19.1,
+        default_temperature: ROOM_TEMPERATURE,
+        heat_conductivity: 251,
+        low_transition: None,
+        high_transition: None,
+        life: 0,
+        decays_into: None,
+        combustion: None,
+        explosion: None,
+        neutron_absorption: 60,
+        neutron_scattering: 0,
+        radioactivity: Some(Radioactivity {
+            emission_chance: 0,
+            fission_chance: 5,
+            fission_neutrons: 2,
+            consumed: false,
+            heat: 200.0,
+        }),
+        fusion: None,
+        fan: None,
+        flags: 0,
+    },
+    Element {
+        variant: ParticleVariant::PLUT,
+        name: "Plutonium",
+        code: "PLUT",
+        description:
+            "Heavy particles. Fissile. Gives off neutrons on its own, and more when hit by them.",
+        colour: [0x40, 0x70, 0x20, 0xff],
+        colour_variation: 8,
+        physics_type: PhysicsType::DYNAMIC,
+        density: 19.8,
+        default_temperature: ROOM_TEMPERATURE,
+        heat_conductivity: 251,
+        low_transition: None,
+        high_transition: None,
+        life: 0,
+        decays_into: None,
+        combustion: None,
+        explosion: None,
+        neutron_absorption: 50,
+        neutron_scattering: 0,
+        radioactivity: Some(Radioactivity {
+            emission_chance: 5,
+            fission_chance: 60,
+            fission_neutrons: 2,
+            consumed: false,
+            heat: 150.0,
+        }),
+        fusion: None,
+        fan: None,
+        flags: 0,
+    },
+    Element {
+        variant: ParticleVariant::DEUT,
+        name: "Deuterium",
+        code: "DEUT",
+        description: "Deuterium oxide. Volume changes with pressure, radioactive with neutrons.",
+        colour: [0x00, 0x15, 0x3f, 0xff],
+        colour_variation: 6,
+        physics_type: PhysicsType::LIQUID,
+        density: 1.1,
+        default_temperature: ROOM_TEMPERATURE,
+        heat_conductivity: 251,
+        low_transition: None,
+        high_transition: None,
+        life: 0,
+        decays_into: None,
+        combustion: None,
+        explosion: None,
+        neutron_absorption: 40,
+        neutron_scattering: 10,
+        radioactivity: Some(Radioactivity {
+            emission_chance: 0,
+            fission_chance: 100,
+            fission_neutrons: 2,
+            consumed: true,
+            heat: 100.0,
+        }),
+        fusion: Some(Fusion {
+            temperature: 2273.15,
+            pressure: 8.0,
+            product: ParticleVariant::PLSM,
+            heat: 3000.0,
+        }),
+        fan: None,
+        flags: PROP_COMPRESSIBLE,
+    },
+    Element {
+        variant: ParticleVariant::C4,
+        name: "C-4",
+        code: "C4",
+        description: "Solid. Explodes when heated or touched by fire, a spark or anything burning.",
+        colour: [0xd0, 0x80, 0xe0, 0xff],
+        colour_variation: 0,
+        physics_type: PhysicsType::STATIC,
+        density: 1.6,
+        default_temperature: ROOM_TEMPERATURE,
+        heat_conductivity: 88,
+        low_transition: None,
+        high_transition: None,
+        life: 0,
+        decays_into: None,
+        combustion: None,
+        explosion: Some(Explosion {
+            detonation_temperature: 673.15,
+            radius: 6.0,
+            impulse: 40.0,
+            heat: 600.0,
+            pressure: 10.0,
+        }),
+        neutron_absorption: 5,
+        neutron_scattering: 5,
+        radioactivity: None,
+        fusion: None,
+        fan: None,
+        flags: 0,
+    },
+    Element {
+        variant: ParticleVariant::WATR,
+        name: "Water",
+        code: "WATR",
+        description: "Liquid. Freezes into ice and boils into steam.",
+        colour: [0x20, 0x20, 0xff, 0xff],
+        colour_variation: 6,
+        physics_type: PhysicsType::LIQUID,
+        density: 1.0,
+        default_temperature: ROOM_TEMPERATURE,
+        heat_conductivity: 29,
+        low_transition: Some(Transition {
+            temperature: 273.15,
+            into: ParticleVariant::ICE,
+        }),
+        high_transition: Some(Transition {
+            temperature: 373.15,
+            into: ParticleVariant::STEM,
+        }),
+        life: 0,
+        decays_into: None,
+        combustion: None,
+        explosion: None,
+        neutron_absorption: 5,
+        neutron_scattering: 30,
+        radioactivity: None,
+        fusion: None,
+        fan: None,
+        flags: 0,
+    },
+    Element {
+        variant: ParticleVariant::ICE,
+        name: "Ice",
+        code: "ICE",
+        description: "Solid. Freezes water, melts back into it.",
+        colour: [0xa0, 0xc0, 0xff, 0xff],
+        colour_variation: 8,
+        physics_type: PhysicsType::STATIC,
+        density: 0.9,
+        default_temperature: 253.15,
+        heat_conductivity: 46,
+        low_transition: None,
+        high_transition: Some(Transition {
+            temperature: 274.15,
+            into: ParticleVariant::WATR,
+        }),
+        life: 0,
+        decays_into: None,
+        combustion: None,
+        explosion: None,
+        neutron_absorption: 5,
+        neutron_scattering: 30,
+        radioactivity: None,
+        fusion: None,
+        fan: None,
+        flags: PROP_BREAKABLE,
+    },
+    Element {
+        variant: ParticleVariant::STEM,
+        name: "Steam",
+        code: "STEM",
+        description: "Gas. Condenses back into water as it cools.",
+        colour: [0xa0, 0xa0, 0xff, 0xff],
+        colour_variation: 10,
+        physics_type: PhysicsType::GAS,
+        density: 0.6,
+        default_temperature: 400.15,
+        heat_conductivity: 48,
+        low_transition: Some(Transition {
+            temperature: 371.15,
+            into: ParticleVariant::WATR,
+        }),
+        high_transition: None,
+        life: 0,
+        decays_into: None,
+        combustion: None,
+        explosion: None,
+        neutron_absorption: 0,
+        neutron_scattering: 5,
+        radioactivity: None,
+        fusion: None,
+        fan: None,
+        flags: 0,
+    },
+    Element {
+        variant: ParticleVariant::LAVA,
+        name: "Lava",
+        code: "LAVA",
+        description: "Molten stone. Cools back into stone.",
+        colour: [0xe0, 0x50, 0x10, 0xff],
+        colour_variation: 20,
+        physics_type: PhysicsType::LIQUID,
+        density: 3.1,
+        default_temperature: 1773.15,
+        heat_conductivity: 60,
+        low_transition: Some(Transition {
+            temperature: 973.15,
+            into: ParticleVariant::STNE,
+        }),
+        high_transition: None,
+        life: 0,
+        decays_into: None,
+        combustion: None,
+        explosion: None,
+        neutron_absorption: 10,
+        neutron_scattering: 20,
+        radioactivity: None,
+        fusion: None,
+        fan: None,
+        flags: PROP_IGNITER,
+    },
+    Element {
+        variant: ParticleVariant::FIRE,
+        name: "Fire",
+        code: "FIRE",
+        description: "Ignites flammable materials. Heats air.",
+        colour: [0xff, 0x60, 0x10, 0xff],
+        colour_variation: 30,
+        physics_type: PhysicsType::GAS,
+        density: 0.1,
+        default_temperature: 1273.15,
+        heat_conductivity: 88,
+        low_transition: None,
+        high_transition: None,
+        life: 40,
+        decays_into: Some(ParticleVariant::SMKE),
+        combustion: None,
+        explosion: None,
+        neutron_absorption: 0,
+        neutron_scattering: 0,
+        radioactivity: None,
+        fusion: None,
+        fan: None,
+        flags: PROP_IGNITER,
+    },
+    Element {
+        variant: ParticleVariant::SMKE,
+        name: "Smoke",
+        code: "SMKE",
+        description: "Left behind by fire. Slowly disperses.",
+        colour: [0x30, 0x30, 0x30, 0xff],
+        colour_variation: 10,
+        physics_type: PhysicsType::GAS,
+        density: 0.1,
+        default_temperature: 373.15,
+        heat_conductivity: 88,
+        low_transition: None,
+        high_transition: None,
+        life: 80,
+        decays_into: None,
+        combustion: None,
+        explosion: None,
+        neutron_absorption: 0,
+        neutron_scattering: 0,
+        radioactivity: None,
+        fusion: None,
+        fan: None,
+        flags: 0,
+    },
+    Element {
+        variant: ParticleVariant::ASH,
+        name: "Ash",
+        code: "ASH",
+        description: "Light powder left behind by burnt wood.",
+        colour: [0x60, 0x5a, 0x55, 0xff],
+        colour_variation: 12,
+        physics_type: PhysicsType::DYNAMIC,
+        density: 0.4,
+        default_temperature: ROOM_TEMPERATURE,
+        heat_conductivity: 70,
+        low_transition: None,
+        high_transition: None,
+        life: 0,
+        decays_into: None,
+        combustion: None,
+        explosion: None,
+        neutron_absorption: 5,
+        neutron_scattering: 5,
+        radioactivity: None,
+        fusion: None,
+        fan: None,
+        flags: 0,
+    },
+    Element {
+        variant: ParticleVariant::SPRK,
+        name: "Spark",
+        code: "SPRK",
+        description: "Short-lived electric spark. Sets off explosives.",
+        colour: [0xff, 0xff, 0x80, 0xff],
+        colour_variation: 0,
+        physics_type: PhysicsType::STATIC,
+        density: 0.1,
+        default_temperature: ROOM_TEMPERATURE,
+        heat_conductivity: 0,
+        low_transition: None,
+        high_transition: None,
+        life: 4,
+        decays_into: None,
+        combustion: None,
+        explosion: None,
+        neutron_absorption: 0,
+        neutron_scattering: 0,
+        radioactivity: None,
+        fusion: None,
+        fan: None,
+        flags: PROP_IGNITER,
+    },
+    Element {
+        variant: ParticleVariant::NEUT,
+        name: "Neutrons",
+        code: "NEUT",
+        description:
+            "Travel in straight lines. Absorbed or scattered by matter, split fissile nuclei.",
+        colour: [0x20, 0xe0, 0xff, 0xff],
+        colour_variation: 0,
+        physics_type: PhysicsType::ENERGY,
+        density: 0.01,
+        default_temperature: ROOM_TEMPERATURE,
+        heat_conductivity: 0,
+        low_transition: None,
+        high_transition: None,
+        life: 300,
+        decays_into: None,
+        combustion: None,
+        explosion: None,
+        neutron_absorption: 0,
+        neutron_scattering: 0,
+        radioactivity: None,
+        fusion: None,
+        fan: None,
+        flags: 0,
+    },
+    Element {
+        variant: ParticleVariant::PLSM,
+        name: "Plasma",
+        code: "PLSM",
+        description: "Extremely hot ionised gas left behind by fusion.",
+        colour: [0xbb, 0x99, 0xff, 0xff],
+        colour_variation: 24,
+        physics_type: PhysicsType::GAS,
+        density: 0.01,
+        default_temperature: MAX_TEMPERATURE,
+        heat_conductivity: 5,
+        low_transition: None,
+        high_transition: None,
+        life: 60,
+        decays_into: None,
+        combustion: None,
+        explosion: None,
+        neutron_absorption: 0,
+        neutron_scattering: 0,
+        radioactivity: None,
+        fusion: None,
+        fan: None,
+        flags: PROP_IGNITER,
+    },
+    Element {
+        variant: ParticleVariant::FAN,
+        name: "Fan",
+        code: "FAN",
+        description: "Solid. Blows the air around it upwards.",
+        colour: [0x80, 0x80, 0xff, 0xff],
+        colour_variation: 0,
+        physics_type: PhysicsType::STATIC,
+        density: 2.0,
+        default_temperature: ROOM_TEMPERATURE,
+        heat_conductivity: 40,
+        low_transition: None,
+        high_transition: None,
+        life: 0,
+        decays_into: None,
+        combustion: None,
+        explosion: None,
+        neutron_absorption: 5,
+        neutron_scattering: 10,
+        radioactivity: None,
+        fusion: None,
+        fan: Some((0.0, -2.0)),
+        flags: PROP_BREAKABLE,
+    },
+];
+
+// Lookups index `ELEMENTS` by variant, so every entry must sit at its variant's index
+const _: () = {
+    let mut i = 0;
+
+    while i < ELEMENTS.len() {
+        assert!(
+            ELEMENTS[i].variant as usize == i,
+            "ELEMENTS must be in ParticleVariant order"
+        );
+        i += 1;
+    }
+};
+
+impl Element {
+    /// Whether this element has the given `PROP_*` flag set.
+    pub fn has_flag(&self, flag: u32) -> bool {
+        self.flags & flag != 0
+    }
+
+    /// Pick the colour of a new particle of this element.
+    ///
+    /// The same random offset is applied to each channel so particles vary in brightness but
+    /// keep the element's hue.
+    pub fn random_colour<R: Rng>(&self, rng: &mut R) -> [u8; 4] {
+        if self.colour_variation == 0 {
+            return self.colour;
+        }
+
+        let variation = self.colour_variation as i16;
+        let offset = rng.gen_range(-variation..=variation);
+        let [r, g, b, a] = self.colour;
+        let vary = |channel: u8| (channel as i16 + offset).clamp(0, 255) as u8;
+
+        [vary(r), vary(g), vary(b), a]
+    }
+
+    /// Names of the element's properties shown in the UI: whether it burns, explodes or is
+    /// radioactive, followed by every `PROP_*` flag set on it.
+    pub fn flag_names(&self) -> Vec<&'static str> {
+        let properties = [
+            (self.combustion.is_some(), "flammable"),
+            (self.explosion.is_some(), "explosive"),
+            (self.radioactivity.is_some(), "radioactive"),
+        ];
+
+        properties
+            .into_iter()
+            .filter(|&(set, _)| set)
+            .map(|(_, name)| name)
+            .chain(
+                FLAG_NAMES
+                    .iter()
+                    .filter(|(flag, _)| self.has_flag(*flag))
+                    .map(|(_, name)| *name),
+            )
+            .collect()
+    }
+}
+
+impl ParticleVariant {
+    /// Look up this variant's entry in the element registry.
+    pub fn element(self) -> &'static Element {
+        &ELEMENTS[self as usize]
+    }
+}
@@ -0,0 +1,29 @@
+//! Detonation of explosive elements.
+
+use super::{
+    element::PROP_IGNITER,
+    occupancy::{Occupancy, NEIGHBOURS},
+    particle::Particle,
+    reaction::Reaction,
+};
+
+/// Detonate explosives that are too hot, or touching fire, a spark or anything burning.
+pub fn detonate(particles: &[Particle], cells: &Occupancy, reactions: &mut Vec<Reaction>) {
+    for (i, particle) in particles.iter().enumerate() {
+        let explosion = match &particle.element().explosion {
+            Some(explosion) => explosion,
+            None => continue,
+        };
+
+        let (x, y) = particle.cell();
+        let ignited = NEIGHBOURS.iter().any(|(dx, dy)| {
+            cells.get(x + dx, y + dy).is_some_and(|j| {
+                particles[j].burning || particles[j].element().has_flag(PROP_IGNITER)
+            })
+        });
+
+        if ignited || particle.temperature > explosion.detonation_temperature {
+            reactions.push(Reaction::Explode(i));
+        }
+    }
+}
@@ -0,0 +1,80 @@
+//! Movement rules for liquids and gases on top of the rigid body simulation.
+
+use rand::Rng;
+use rapier2d::prelude::*;
+
+use super::{
+    gravity::GravityField,
+    occupancy::Occupancy,
+    particle::{Particle, PhysicsType},
+    reaction::Reaction,
+};
+
+/// Chance out of 100 each step that a resting liquid particle flows sideways.
+const SPREAD_CHANCE: u8 = 50;
+/// Sideways speed given to a liquid particle flowing into a free cell, in pixels per second.
+const SPREAD_SPEED: f32 = 8.0;
+/// Largest random velocity change given to a gas particle each step, in pixels per second.
+const DIFFUSION_SPEED: f32 = 4.0;
+
+/// Spread liquids sideways, diffuse gases, and let denser fluids sink through lighter ones, with
+/// down and sideways taken from the gravity where each particle is.
+pub fn flow(
+    particles: &[Particle],
+    cells: &Occupancy,
+    gravity: &GravityField,
+    reactions: &mut Vec<Reaction>,
+) {
+    let mut rng = rand::thread_rng();
+
+    for (i, particle) in particles.iter().enumerate() {
+        let element = particle.element();
+
+        if !element.physics_type.is_fluid() {
+            continue;
+        }
+
+        let (x, y) = particle.cell();
+        let (dx, dy) = gravity.cell_direction_at(particle.x, particle.y);
+        // Without gravity nothing sinks or settles
+        let below_cell = if (dx, dy) == (0, 0) {
+            None
+        } else {
+            Some((x + dx, y + dy))
+        };
+
+        if let Some(j) = below_cell.and_then(|(bx, by)| cells.get(bx, by)) {
+            let below = particles[j].element();
+
+            if below.physics_type.is_fluid() && element.density > below.density {
+                reactions.push(Reaction::Swap(i, j));
+                continue;
+            }
+        }
+
+        match element.physics_type {
+            PhysicsType::LIQUID => {
+                let resting = below_cell.is_some_and(|(bx, by)| cells.is_occupied(bx, by));
+
+                if resting && rng.gen_range(0..100) < SPREAD_CHANCE {
+                    // A quarter turn either way from down
+                    let side = if rng.gen_bool(0.5) { 1 } else { -1 };
+                    let (sx, sy) = (-side * dy, side * dx);
+
+                    if !cells.is_occupied(x + sx, y + sy) {
+                        let push = vector![sx as f32, sy as f32].normalize() * SPREAD_SPEED;
+                        reactions.push(Reaction::Push(i, push.x, push.y));
+                    }
+                }
+            }
+            PhysicsType::GAS => {
+                reactions.push(Reaction::Push(
+                    i,
+                    rng.gen_range(-DIFFUSION_SPEED..=DIFFUSION_SPEED),
+                    rng.gen_range(-DIFFUSION_SPEED..=DIFFUSION_SPEED),
+                ));
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,84 @@
+//! Compression and fusion of elements under pressure.
+
+use std::collections::HashSet;
+
+use super::{
+    air::AirGrid,
+    element::PROP_COMPRESSIBLE,
+    occupancy::{Occupancy, NEIGHBOURS},
+    particle::{Particle, ParticleVariant},
+    reaction::Reaction,
+};
+
+/// Air pressure above which compressible particles pack their neighbours into themselves.
+const COMPRESSION_PRESSURE: f32 = 4.0;
+/// Air pressure below which compressed particles release what they have packed.
+const DECOMPRESSION_PRESSURE: f32 = 1.0;
+
+/// Fuse particles that are hot and compressed enough, and compress or decompress the rest of
+/// the compressible particles with the air pressure around them.
+pub fn fuse(
+    particles: &mut [Particle],
+    cells: &Occupancy,
+    air: &AirGrid,
+    reactions: &mut Vec<Reaction>,
+) {
+    // Particles already packed into another, or packing others, this step
+    let mut packed = HashSet::new();
+
+    for i in 0..particles.len() {
+        let element = particles[i].element();
+        let pressure = air.pressure_at(particles[i].x, particles[i].y);
+
+        if let Some(fusion) = &element.fusion {
+            if particles[i].temperature > fusion.temperature && pressure > fusion.pressure {
+                particles[i].temperature += fusion.heat * (1 + particles[i].compression) as f32;
+
+                reactions.push(Reaction::Change(i, fusion.product));
+                reactions.push(Reaction::Spawn(
+                    particles[i].x,
+                    particles[i].y,
+                    ParticleVariant::NEUT,
+                ));
+
+                continue;
+            }
+        }
+
+        if !element.has_flag(PROP_COMPRESSIBLE) || packed.contains(&i) {
+            continue;
+        }
+
+        let (x, y) = particles[i].cell();
+
+        if pressure > COMPRESSION_PRESSURE {
+            let neighbour = NEIGHBOURS.iter().find_map(|(dx, dy)| {
+                cells.get(x + dx, y + dy).filter(|&j| {
+                    particles[j].variant() == particles[i].variant() && !packed.contains(&j)
+                })
+            });
+
+            if let Some(j) = neighbour {
+                particles[i].compression += 1 + particles[j].compression;
+                packed.insert(i);
+                packed.insert(j);
+
+                reactions.push(Reaction::Remove(j));
+            }
+        } else if pressure < DECOMPRESSION_PRESSURE && particles[i].compression > 0 {
+            let free = NEIGHBOURS
+                .iter()
+                .find(|(dx, dy)| !cells.is_occupied(x + dx, y + dy));
+
+            if let Some((dx, dy)) = free {
+                particles[i].compression -= 1;
+
+                reactions.push(Reaction::Spawn(
+                    (x + dx) as f32,
+                    (y + dy) as f32,
+                    particles[i].variant(),
+                ));
+            }
+        }
+    }
+}
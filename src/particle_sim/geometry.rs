@@ -11,6 +11,7 @@ pub(crate) struct Point {
 }
 
 /// A tiny rectangle based on two absolute `Point`s.
+#[allow(dead_code)]
 #[derive(Copy, Clone, Debug, Default)]
 pub(crate) struct Rect {
     pub(crate) p1: Point,
@@ -40,6 +41,7 @@ impl std::ops::Mul for Point {
     }
 }
 
+#[allow(dead_code)]
 impl Rect {
     /// Create a rectangle from two `Point`s.
     pub(crate) fn new(p1: &Point, p2: &Point) -> Rect {
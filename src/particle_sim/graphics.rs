@@ -58,14 +58,19 @@ impl Drawable for Sprite {
     }
 }
 
-/// Blit a drawable to the pixel buffer.
-pub(crate) fn blit<S>(screen: &mut [u8], dest: &Point, sprite: &S, colour: [u8; 4])
-where
+/// Blit a drawable to a pixel buffer `screen_width` pixels wide.
+pub(crate) fn blit<S>(
+    screen: &mut [u8],
+    screen_width: usize,
+    dest: &Point,
+    sprite: &S,
+    colour: [u8; 4],
+) where
     S: Drawable,
 {
-    if (dest.x + sprite.width() > crate::WIDTH as usize)
-        || (dest.y + sprite.height() > crate::HEIGHT as usize)
-    {
+    let screen_height = screen.len() / 4 / screen_width;
+
+    if (dest.x + sprite.width() > screen_width) || (dest.y + sprite.height() > screen_height) {
         return;
     }
 
@@ -74,20 +79,16 @@ where
 
     let mut s = 0;
     for y in 0..sprite.height() {
-        let i = dest.x * 4 + dest.y * crate::WIDTH as usize * 4 + y * crate::WIDTH as usize * 4;
-
-        // Bit of a hacky way to get force the sprite to be drawn with the specified colour - doesn't support transparency only solid colours.
-        let new_pixels = [
-            colour[0], colour[1], colour[2], colour[3], colour[0], colour[1], colour[2], colour[3],
-            colour[0], colour[1], colour[2], colour[3], colour[0], colour[1], colour[2], colour[3],
-        ];
+        let i = dest.x * 4 + dest.y * screen_width * 4 + y * screen_width * 4;
 
-        // Merge pixels from sprite into screen
-        let zipped = screen[i..i + width].iter_mut().zip(&new_pixels[0..width]);
+        // The sprite is only used as a mask so it can be drawn with the specified colour - doesn't support transparency only solid colours.
+        let zipped = screen[i..i + width]
+            .chunks_exact_mut(4)
+            .zip(pixels[s..s + width].chunks_exact(4));
 
-        for (left, &right) in zipped {
-            if right > 0 {
-                *left = right;
+        for (left, right) in zipped {
+            if right[3] > 0 {
+                left.copy_from_slice(&colour);
             }
         }
 
@@ -95,28 +96,30 @@ where
     }
 }
 
-/// Draw a line to the pixel buffer using Bresenham's algorithm.
-pub(crate) fn line(screen: &mut [u8], p1: &Point, p2: &Point, color: [u8; 4]) {
+/// Draw a line to a pixel buffer `screen_width` pixels wide using Bresenham's algorithm.
+pub(crate) fn line(screen: &mut [u8], screen_width: usize, p1: &Point, p2: &Point, color: [u8; 4]) {
+    let screen_height = screen.len() / 4 / screen_width;
     let p1 = (p1.x as i64, p1.y as i64);
     let p2 = (p2.x as i64, p2.y as i64);
 
     for (x, y) in Bresenham::new(p1, p2) {
-        let x = std::cmp::min(x as usize, crate::WIDTH as usize - 1);
-        let y = std::cmp::min(y as usize, crate::HEIGHT as usize - 1);
-        let i = x * 4 + y * crate::WIDTH as usize * 4;
+        let x = std::cmp::min(x as usize, screen_width - 1);
+        let y = std::cmp::min(y as usize, screen_height - 1);
+        let i = x * 4 + y * screen_width * 4;
 
         screen[i..i + 4].copy_from_slice(&color);
     }
 }
 
-/// Draw a rectangle to the pixel buffer using two points in opposite corners.
-pub(crate) fn rect(screen: &mut [u8], p1: &Point, p2: &Point, color: [u8; 4]) {
+/// Draw a rectangle to a pixel buffer `screen_width` pixels wide using two points in opposite
+/// corners.
+pub(crate) fn rect(screen: &mut [u8], screen_width: usize, p1: &Point, p2: &Point, color: [u8; 4]) {
     let p2 = Point::new(p2.x - 1, p2.y - 1);
     let p3 = Point::new(p1.x, p2.y);
     let p4 = Point::new(p2.x, p1.y);
 
-    line(screen, p1, &p3, color);
-    line(screen, &p3, &p2, color);
-    line(screen, &p2, &p4, color);
-    line(screen, &p4, p1, color);
+    line(screen, screen_width, p1, &p3, color);
+    line(screen, screen_width, &p3, &p2, color);
+    line(screen, screen_width, &p2, &p4, color);
+    line(screen, screen_width, &p4, p1, color);
 }
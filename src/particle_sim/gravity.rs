@@ -0,0 +1,299 @@
+//! Gravity modes, and the field of accelerations they produce across the world.
+
+use rapier2d::prelude::*;
+
+use super::particle::{Particle, PhysicsType};
+
+/// Strength of standard downward gravity, in pixels per second squared.
+pub const STANDARD_GRAVITY: f32 = 9.81;
+/// Width and height of a cell of the Newtonian gravity field, in world pixels.
+pub const CELL_SIZE: usize = 8;
+/// Density above which an element is heavy enough to attract other particles.
+const HEAVY_DENSITY: f32 = 10.0;
+/// Acceleration towards a cell per unit of mass in it, over the squared distance to it.
+const GRAVITATIONAL_CONSTANT: f32 = 8.0;
+/// Added to squared distances so the pull of a cell on itself and its neighbours stays finite.
+const SOFTENING: f32 = (CELL_SIZE * CELL_SIZE) as f32;
+/// Width and height in cells of the blocks whose mass is lumped together at its centre of mass
+/// when pulling on cells outside the neighbouring blocks.
+const BLOCK_SIZE: usize = 4;
+
+/// The gravity mode of a world. Newtonian gravity from heavy elements can be added on top of any
+/// of them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Gravity {
+    /// The same acceleration everywhere, in pixels per second squared.
+    Directional(Vector<Real>),
+    /// No gravity at all.
+    Off,
+    /// Pulled towards the centre of the world with the given strength.
+    Radial(f32),
+}
+
+impl Gravity {
+    /// Names accepted by `from_name`, in the order `next` cycles through them. A direction and
+    /// strength can also be given as `<x>,<y>`.
+    pub const NAMES: [&'static str; 3] = ["down", "off", "radial"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "down" => Some(Self::default()),
+            "off" => Some(Gravity::Off),
+            "radial" => Some(Gravity::Radial(STANDARD_GRAVITY)),
+            _ => {
+                let (x, y) = name.split_once(',')?;
+                let g: Vector<Real> = vector![x.parse().ok()?, y.parse().ok()?];
+
+                // Rapier can't cope with `nan` or `inf`, which parse as floats
+                if g.iter().all(|component| component.is_finite()) {
+                    Some(Gravity::Directional(g))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    pub fn name(self) -> String {
+        match self {
+            Gravity::Directional(g) if g == vector![0.0, STANDARD_GRAVITY] => {
+                String::from(Self::NAMES[0])
+            }
+            Gravity::Directional(g) => format!("{},{}", g.x, g.y),
+            Gravity::Off => String::from(Self::NAMES[1]),
+            Gravity::Radial(_) => String::from(Self::NAMES[2]),
+        }
+    }
+
+    /// The next mode, wrapping around after the last. Any direction moves on to no gravity.
+    pub fn next(self) -> Self {
+        match self {
+            Gravity::Directional(_) => Gravity::Off,
+            Gravity::Off => Gravity::Radial(STANDARD_GRAVITY),
+            Gravity::Radial(_) => Self::default(),
+        }
+    }
+}
+
+impl Default for Gravity {
+    fn default() -> Self {
+        Gravity::Directional(vector![0.0, STANDARD_GRAVITY])
+    }
+}
+
+/// The acceleration due to gravity across the world, stored row-major at `CELL_SIZE`
+/// resolution unless it is the same everywhere.
+pub struct GravityField {
+    width: usize,
+    height: usize,
+    /// Centre of the world, which radial gravity pulls towards.
+    centre: Vector<Real>,
+    /// The acceleration everywhere, if gravity is uniform.
+    uniform: Option<Vector<Real>>,
+    cells: Vec<Vector<Real>>,
+}
+
+impl GravityField {
+    /// Create a field of standard gravity over a world of the given size in pixels.
+    pub fn new(world_width: usize, world_height: usize) -> Self {
+        let width = world_width.div_ceil(CELL_SIZE);
+        let height = world_height.div_ceil(CELL_SIZE);
+
+        GravityField {
+            width,
+            height,
+            centre: vector![world_width as f32, world_height as f32] / 2.0,
+            uniform: Some(vector![0.0, STANDARD_GRAVITY]),
+            cells: vec![Vector::zeros(); width * height],
+        }
+    }
+
+    /// The acceleration everywhere, if gravity is the same across the whole world.
+    pub fn uniform(&self) -> Option<Vector<Real>> {
+        self.uniform
+    }
+
+    /// The acceleration at the given world position. Positions outside the world get the
+    /// acceleration of the nearest cell.
+    pub fn at(&self, x: f32, y: f32) -> Vector<Real> {
+        if let Some(g) = self.uniform {
+            return g;
+        }
+
+        let cx = (x.max(0.0) as usize / CELL_SIZE).min(self.width - 1);
+        let cy = (y.max(0.0) as usize / CELL_SIZE).min(self.height - 1);
+
+        self.cells[cy * self.width + cx]
+    }
+
+    /// The neighbouring cell gravity points closest to from the given world position, as an
+    /// offset, or `(0, 0)` where there is no gravity.
+    pub fn cell_direction_at(&self, x: f32, y: f32) -> (i32, i32) {
+        let g = self.at(x, y);
+        let strength = g.norm();
+
+        if strength > 0.0 {
+            (
+                (g.x / strength).round() as i32,
+                (g.y / strength).round() as i32,
+            )
+        } else {
+            (0, 0)
+        }
+    }
+
+    /// Recompute the field for the given mode, adding the pull of heavy particles where they are
+    /// now if `newtonian` is set.
+    pub fn update(&mut self, gravity: Gravity, newtonian: bool, particles: &[Particle]) {
+        match gravity {
+            Gravity::Directional(g) => self.uniform = Some(g),
+            Gravity::Off => self.uniform = Some(Vector::zeros()),
+            Gravity::Radial(strength) => {
+                let centre = self.centre;
+
+                self.fill(|position| {
+                    let offset = centre - position;
+                    let distance = offset.norm();
+
+                    if distance > 0.0 {
+                        offset / distance * strength
+                    } else {
+                        Vector::zeros()
+                    }
+                });
+            }
+        }
+
+        if newtonian {
+            self.add_newtonian(particles);
+        }
+    }
+
+    /// Add the pull of heavy particles towards each other to the field.
+    fn add_newtonian(&mut self, particles: &[Particle]) {
+        let blocks = self.blocks(particles);
+
+        if blocks.is_empty() {
+            return;
+        }
+
+        if let Some(g) = self.uniform.take() {
+            self.cells.iter_mut().for_each(|cell| *cell = g);
+        }
+
+        // Nearby cells pull on their own, further ones as part of their whole block,
+        // so a full world costs a fraction of summing every pair of cells
+        for cy in 0..self.height {
+            for cx in 0..self.width {
+                let position = self.centre_of(cx as f32 + 0.5, cy as f32 + 0.5);
+                let (bx, by) = (cx / BLOCK_SIZE, cy / BLOCK_SIZE);
+                let mut acceleration = Vector::zeros();
+
+                for block in &blocks {
+                    if block.x.abs_diff(bx) <= 1 && block.y.abs_diff(by) <= 1 {
+                        for &(centre, mass) in &block.cells {
+                            acceleration += pull(position, centre, mass);
+                        }
+                    } else {
+                        acceleration += pull(position, block.centre, block.mass);
+                    }
+                }
+
+                self.cells[cy * self.width + cx] += acceleration;
+            }
+        }
+    }
+
+    /// World position of the centre of the given cell, which may be fractional.
+    fn centre_of(&self, cx: f32, cy: f32) -> Vector<Real> {
+        vector![cx, cy] * CELL_SIZE as f32
+    }
+
+    /// Set every cell to the acceleration at its centre.
+    fn fill(&mut self, acceleration: impl Fn(Vector<Real>) -> Vector<Real>) {
+        for cy in 0..self.height {
+            for cx in 0..self.width {
+                let position = self.centre_of(cx as f32 + 0.5, cy as f32 + 0.5);
+                self.cells[cy * self.width + cx] = acceleration(position);
+            }
+        }
+
+        self.uniform = None;
+    }
+
+    /// Every block of cells holding heavy particles.
+    fn blocks(&self, particles: &[Particle]) -> Vec<Block> {
+        let mut mass = vec![0.0; self.width * self.height];
+
+        for particle in particles {
+            let element = particle.element();
+
+            if element.physics_type == PhysicsType::ENERGY || element.density < HEAVY_DENSITY {
+                continue;
+            }
+
+            if particle.x < 0.0 || particle.y < 0.0 {
+                continue;
+            }
+
+            let cx = particle.x as usize / CELL_SIZE;
+            let cy = particle.y as usize / CELL_SIZE;
+
+            if cx < self.width && cy < self.height {
+                mass[cy * self.width + cx] += element.density;
+            }
+        }
+
+        let blocks_wide = self.width.div_ceil(BLOCK_SIZE);
+        let mut block_of = vec![None; blocks_wide * self.height.div_ceil(BLOCK_SIZE)];
+        let mut blocks: Vec<Block> = Vec::new();
+
+        for (i, &mass) in mass.iter().enumerate().filter(|&(_, &mass)| mass > 0.0) {
+            let (cx, cy) = (i % self.width, i / self.width);
+            let (bx, by) = (cx / BLOCK_SIZE, cy / BLOCK_SIZE);
+            let centre = self.centre_of(cx as f32 + 0.5, cy as f32 + 0.5);
+
+            let index = *block_of[by * blocks_wide + bx].get_or_insert_with(|| {
+                blocks.push(Block {
+                    x: bx,
+                    y: by,
+                    cells: Vec::new(),
+                    centre: Vector::zeros(),
+                    mass: 0.0,
+                });
+                blocks.len() - 1
+            });
+
+            let block = &mut blocks[index];
+            block.cells.push((centre, mass));
+            block.centre += centre * mass;
+            block.mass += mass;
+        }
+
+        for block in &mut blocks {
+            block.centre /= block.mass;
+        }
+
+        blocks
+    }
+}
+
+/// The heavy mass in a `BLOCK_SIZE` square of gravity field cells.
+struct Block {
+    /// Position of the block, in blocks.
+    x: usize,
+    y: usize,
+    /// The centre and total mass of each of the block's cells holding heavy particles.
+    cells: Vec<(Vector<Real>, f32)>,
+    /// Centre of mass of the whole block.
+    centre: Vector<Real>,
+    mass: f32,
+}
+
+/// Acceleration at `position` towards a mass at `centre`.
+fn pull(position: Vector<Real>, centre: Vector<Real>, mass: f32) -> Vector<Real> {
+    let offset = centre - position;
+    let distance_squared = offset.norm_squared() + SOFTENING;
+
+    offset * (GRAVITATIONAL_CONSTANT * mass) / (distance_squared * distance_squared.sqrt())
+}
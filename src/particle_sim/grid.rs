@@ -0,0 +1,320 @@
+//! Cellular-automaton falling-sand backend, an alternative to simulating every particle as a
+//! rapier rigid body.
+
+use rand::{seq::SliceRandom, Rng};
+
+use super::{
+    backend::Backend,
+    edge::{EdgeMode, Edges},
+    gravity::{GravityField, STANDARD_GRAVITY},
+    occupancy::Occupancy,
+    particle::{cell_centre, Particle, PhysicsType},
+};
+
+/// Speed above which a particle's velocity moves it a cell, in pixels per second.
+const MIN_SPEED: f32 = 20.0;
+/// Fraction of a particle's velocity kept per step.
+const VELOCITY_DAMPING: f32 = 0.9;
+
+/// Where a particle moving to a cell ends up.
+enum Target {
+    /// A cell inside the grid, after wrapping around the edges.
+    Cell(i32, i32),
+    /// Outside the world, through a void edge.
+    Off,
+    /// Nowhere, because a solid edge is in the way.
+    Blocked,
+}
+
+/// An occupancy grid over the world, moving particles one cell at a time by per-cell rules.
+///
+/// Powders fall straight down or slide off diagonally so piles form slopes, liquids also flow
+/// sideways, and gases drift upwards, where down is whichever of the eight neighbouring cells
+/// gravity points closest to. Weaker gravity moves particles less often, and without any, gases
+/// drift in every direction while everything else stays put. Denser fluids sink through lighter
+/// ones. New particles are snapped to the centre of the cell they land in, and can't be added on
+/// top of another particle.
+pub struct GridBackend {
+    width: usize,
+    height: usize,
+    edges: Edges,
+    /// Which particle is in each cell, kept up to date as particles move, so moves can be checked
+    /// against particles that already moved this step. `World` only re-indexes its own copy
+    /// between steps.
+    cells: Occupancy,
+    particles: Vec<Particle>,
+}
+
+impl GridBackend {
+    pub fn new(width: usize, height: usize) -> Self {
+        GridBackend {
+            width,
+            height,
+            edges: Edges::default(),
+            cells: Occupancy::new(width, height),
+            particles: Vec::new(),
+        }
+    }
+
+    /// Where a particle moving to the given cell ends up.
+    fn target(&self, x: i32, y: i32) -> Target {
+        let (width, height) = (self.width as i32, self.height as i32);
+        let beyond = self
+            .edges
+            .beyond(x as f32, y as f32, width as f32, height as f32);
+
+        if beyond.contains(&Some(EdgeMode::Solid)) {
+            Target::Blocked
+        } else if beyond.contains(&Some(EdgeMode::Void)) {
+            Target::Off
+        } else {
+            Target::Cell(x.rem_euclid(width), y.rem_euclid(height))
+        }
+    }
+}
+
+impl Backend for GridBackend {
+    fn name(&self) -> &'static str {
+        "grid"
+    }
+
+    fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    fn step(&mut self, dt: f32, gravity: &GravityField) {
+        let mut rng = rand::thread_rng();
+        // Taken out for the step so the grid can be borrowed alongside it
+        let mut particles = std::mem::take(&mut self.particles);
+
+        // `World` may have moved particles through `particles_mut` since the last step
+        self.cells.rebuild(&particles);
+
+        // How far along gravity each particle is
+        let depth: Vec<f32> = particles
+            .iter()
+            .map(|particle| {
+                let g = gravity.at(particle.x, particle.y);
+                let (x, y) = particle.cell();
+
+                x as f32 * g.x + y as f32 * g.y
+            })
+            .collect();
+
+        // Update from the bottom up so falling particles don't move twice, in a random order
+        // within each row so nothing drifts to one side
+        let mut order: Vec<usize> = (0..particles.len()).collect();
+        order.shuffle(&mut rng);
+        order.sort_by(|&a, &b| depth[b].total_cmp(&depth[a]));
+
+        for i in order {
+            let physics_type = particles[i].element().physics_type;
+
+            match physics_type {
+                PhysicsType::STATIC => continue,
+                PhysicsType::ENERGY => {
+                    particles[i].x += particles[i].vx * dt;
+                    particles[i].y += particles[i].vy * dt;
+                    continue;
+                }
+                _ => {}
+            }
+
+            let (x, y) = particles[i].cell();
+
+            // Particles that left the world are about to be deleted or wrapped by `World`
+            if !self.cells.contains(x, y) {
+                continue;
+            }
+
+            let side = if rng.gen_bool(0.5) { 1 } else { -1 };
+            let g = gravity.at(particles[i].x, particles[i].y);
+            let strength = g.norm();
+
+            let down = if strength > 0.0 && rng.gen::<f32>() < strength / STANDARD_GRAVITY {
+                gravity.cell_direction_at(particles[i].x, particles[i].y)
+            } else {
+                (0, 0)
+            };
+
+            let mut moves = Vec::with_capacity(6);
+
+            // Blasts and wind push the particle a cell along its velocity
+            let (vx, vy) = (particles[i].vx, particles[i].vy);
+
+            if vx * vx + vy * vy > MIN_SPEED * MIN_SPEED {
+                if vx.abs() >= vy.abs() {
+                    moves.push((vx.signum() as i32, 0));
+                } else {
+                    moves.push((0, vy.signum() as i32));
+                }
+            }
+
+            particles[i].vx *= VELOCITY_DAMPING;
+            particles[i].vy *= VELOCITY_DAMPING;
+
+            let (diagonal, other_diagonal) = (turn(down, side), turn(down, -side));
+            let (sideways, other_sideways) = (turn(diagonal, side), turn(other_diagonal, -side));
+            let up = (-down.0, -down.1);
+
+            match physics_type {
+                PhysicsType::GAS if down == (0, 0) => {
+                    let mut drift = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+                    drift.shuffle(&mut rng);
+                    moves.extend_from_slice(&drift);
+                }
+                _ if down == (0, 0) => {}
+                PhysicsType::DYNAMIC => {
+                    moves.extend_from_slice(&[down, diagonal, other_diagonal]);
+                }
+                PhysicsType::LIQUID => {
+                    moves.extend_from_slice(&[
+                        down,
+                        diagonal,
+                        other_diagonal,
+                        sideways,
+                        other_sideways,
+                    ]);
+                }
+                PhysicsType::GAS => {
+                    let mut drift = [
+                        up,
+                        turn(up, side),
+                        turn(up, -side),
+                        sideways,
+                        other_sideways,
+                    ];
+                    drift.shuffle(&mut rng);
+                    moves.extend_from_slice(&drift);
+                }
+                _ => {}
+            }
+
+            for (dx, dy) in moves {
+                let (tx, ty) = match self.target(x + dx, y + dy) {
+                    Target::Cell(tx, ty) => (tx, ty),
+                    Target::Off => {
+                        self.cells.remove(i, &particles[i]);
+                        (particles[i].x, particles[i].y) = cell_centre((x + dx, y + dy));
+                        break;
+                    }
+                    Target::Blocked => continue,
+                };
+
+                match self.cells.get(tx, ty) {
+                    None => {
+                        self.cells.remove(i, &particles[i]);
+                        (particles[i].x, particles[i].y) = cell_centre((tx, ty));
+                        self.cells.insert(i, &particles[i]);
+                        break;
+                    }
+                    Some(j)
+                        if displaces(&particles[i], &particles[j], dx * down.0 + dy * down.1) =>
+                    {
+                        (particles[j].x, particles[j].y) = cell_centre((x, y));
+                        (particles[i].x, particles[i].y) = cell_centre((tx, ty));
+                        self.cells.swap(i, j, &particles);
+                        break;
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+
+        self.particles = particles;
+    }
+
+    fn add(&mut self, mut particle: Particle) -> bool {
+        (particle.x, particle.y) = cell_centre(particle.cell());
+
+        if particle.element().physics_type != PhysicsType::ENERGY {
+            let (x, y) = particle.cell();
+
+            if !self.cells.contains(x, y) || self.cells.is_occupied(x, y) {
+                return false;
+            }
+        }
+
+        self.particles.push(particle);
+        self.cells.insert(
+            self.particles.len() - 1,
+            &self.particles[self.particles.len() - 1],
+        );
+
+        true
+    }
+
+    fn remove(&mut self, index: usize) -> Particle {
+        let last = self.particles.len() - 1;
+        let particle = self.particles.swap_remove(index);
+
+        self.cells.remove(index, &particle);
+
+        if index != last {
+            self.cells.remove(last, &self.particles[index]);
+            self.cells.insert(index, &self.particles[index]);
+        }
+
+        particle
+    }
+
+    fn replace(&mut self, index: usize, particle: Particle) {
+        self.cells.remove(index, &self.particles[index]);
+        self.particles[index] = particle;
+        self.cells.insert(index, &self.particles[index]);
+    }
+
+    fn particles(&self) -> &[Particle] {
+        &self.particles
+    }
+
+    fn particles_mut(&mut self) -> &mut [Particle] {
+        &mut self.particles
+    }
+
+    fn set_edges(&mut self, edges: Edges) {
+        self.edges = edges;
+    }
+
+    fn moves_fluids(&self) -> bool {
+        true
+    }
+
+    fn set_position(&mut self, index: usize, x: f32, y: f32) {
+        self.cells.remove(index, &self.particles[index]);
+        self.particles[index].x = x;
+        self.particles[index].y = y;
+        self.cells.insert(index, &self.particles[index]);
+    }
+
+    fn swap(&mut self, i: usize, j: usize) {
+        let (x, y) = (self.particles[i].x, self.particles[i].y);
+
+        self.particles[i].x = self.particles[j].x;
+        self.particles[i].y = self.particles[j].y;
+        self.particles[j].x = x;
+        self.particles[j].y = y;
+
+        self.cells.swap(i, j, &self.particles);
+    }
+}
+
+/// The cell direction `(dx, dy)` turned 45 degrees clockwise, or anticlockwise if `side` is -1.
+fn turn((dx, dy): (i32, i32), side: i32) -> (i32, i32) {
+    ((dx - side * dy).signum(), (side * dx + dy).signum())
+}
+
+/// Whether `particle` can swap places with the fluid `other` by moving `dy` cells down, along
+/// gravity.
+///
+/// Denser particles sink through lighter fluids and lighter fluids rise through denser ones.
+fn displaces(particle: &Particle, other: &Particle, dy: i32) -> bool {
+    let (element, other) = (particle.element(), other.element());
+
+    if !other.physics_type.is_fluid() {
+        return false;
+    }
+
+    (dy > 0 && element.density > other.density)
+        || (dy < 0 && element.physics_type.is_fluid() && element.density < other.density)
+}
@@ -0,0 +1,58 @@
+//! Heat conduction between touching particles and the ambient air layer.
+
+use super::{air::AirGrid, occupancy::Occupancy, particle::Particle};
+
+/// Default temperature of new particles and the air, in Kelvin.
+pub const ROOM_TEMPERATURE: f32 = 295.15;
+pub const MIN_TEMPERATURE: f32 = 0.0;
+pub const MAX_TEMPERATURE: f32 = 9999.0;
+
+/// Fraction of the temperature difference exchanged per step by two perfect conductors.
+const PARTICLE_TRANSFER_RATE: f32 = 0.25;
+/// Fraction of the temperature difference exchanged per step between a particle and its air cell.
+const AIR_TRANSFER_RATE: f32 = 0.02;
+/// How many particles' worth of heat a single air cell holds.
+const AIR_HEAT_CAPACITY: f32 = 4.0;
+
+/// Neighbour offsets visited from each particle, chosen so every touching pair is visited once.
+const HALF_NEIGHBOURS: [(i32, i32); 4] = [(1, 0), (-1, 1), (0, 1), (1, 1)];
+
+/// Conduct heat between touching particles, and between each particle and the air around it.
+pub fn conduct_heat(particles: &mut [Particle], cells: &Occupancy, air: &mut AirGrid) {
+    for i in 0..particles.len() {
+        let (x, y) = particles[i].cell();
+        let conductivity = particles[i].element().heat_conductivity as f32 / 255.0;
+
+        if conductivity == 0.0 {
+            continue;
+        }
+
+        for (dx, dy) in HALF_NEIGHBOURS {
+            let j = match cells.get(x + dx, y + dy) {
+                Some(j) => j,
+                None => continue,
+            };
+
+            let pair_conductivity =
+                conductivity * particles[j].element().heat_conductivity as f32 / 255.0;
+            let flow = (particles[j].temperature - particles[i].temperature)
+                * pair_conductivity
+                * PARTICLE_TRANSFER_RATE;
+
+            particles[i].temperature += flow;
+            particles[j].temperature -= flow;
+        }
+
+        if let Some(cell) = air.cell_index(particles[i].x, particles[i].y) {
+            let flow =
+                (air.heat[cell] - particles[i].temperature) * conductivity * AIR_TRANSFER_RATE;
+
+            particles[i].temperature += flow;
+            air.heat[cell] -= flow / AIR_HEAT_CAPACITY;
+        }
+    }
+
+    for particle in particles.iter_mut() {
+        particle.temperature = particle.temperature.clamp(MIN_TEMPERATURE, MAX_TEMPERATURE);
+    }
+}
@@ -11,12 +11,18 @@ use crate::particle_sim::graphics::{CachedSprite, Frame};
 pub(crate) struct Assets {
     // sounds: TODO
     sprites: HashMap<Frame, CachedSprite>,
+    brushes: Vec<(&'static str, CachedSprite)>,
 }
 
 impl Assets {
     pub(crate) fn sprites(&self) -> &HashMap<Frame, CachedSprite> {
         &self.sprites
     }
+
+    /// Bitmap brushes and their names.
+    pub(crate) fn brushes(&self) -> &[(&'static str, CachedSprite)] {
+        &self.brushes
+    }
 }
 
 /// Load all static assets into an `Assets` structure
@@ -27,11 +33,19 @@ pub(crate) fn load_assets() -> Assets {
 
     sprites.insert(Particle, load_pcx(include_bytes!("assets/particle.pcx")));
 
-    Assets { sprites }
+    let brushes = vec![
+        ("Star", load_pcx(include_bytes!("assets/brushes/star.pcx"))),
+        (
+            "Spray",
+            load_pcx(include_bytes!("assets/brushes/spray.pcx")),
+        ),
+    ];
+
+    Assets { sprites, brushes }
 }
 
 /// Convert PCX data to raw pixels
-fn load_pcx(pcx: &[u8]) -> CachedSprite {
+pub(crate) fn load_pcx(pcx: &[u8]) -> CachedSprite {
     let mut reader = pcx::Reader::new(Cursor::new(pcx)).unwrap();
     let width = reader.width() as usize;
     let height = reader.height() as usize;
@@ -42,7 +56,7 @@ fn load_pcx(pcx: &[u8]) -> CachedSprite {
         let mut buffer = Vec::new();
         buffer.resize_with(width * height, Default::default);
         for y in 0..height {
-            let a = y as usize * width;
+            let a = y * width;
             let b = a + width;
             reader.next_row_paletted(&mut buffer[a..b]).unwrap();
         }
@@ -58,9 +72,10 @@ fn load_pcx(pcx: &[u8]) -> CachedSprite {
             .into_iter()
             .flat_map(|pal| {
                 let i = pal as usize * 3;
-                &palette[i..i + 3]
+                let mut rgb = palette[i..i + 3].to_vec();
+                rgb.push(255);
+                rgb
             })
-            .cloned()
             .collect::<Vec<u8>>();
         result.extend_from_slice(&pixels);
     } else {
@@ -0,0 +1,103 @@
+//! Index of which particle occupies each cell of the world.
+
+use super::particle::{Particle, PhysicsType};
+
+/// Offsets of the cells sharing an edge with a cell.
+pub const NEIGHBOURS: [(i32, i32); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+
+/// Marks an empty cell.
+const EMPTY: usize = usize::MAX;
+
+/// The index of the particle in each cell of the world, stored row-major.
+///
+/// Energy particles pass through matter rather than occupying a cell, so are never indexed.
+/// Particles outside the world aren't indexed either.
+pub struct Occupancy {
+    width: usize,
+    height: usize,
+    cells: Vec<usize>,
+}
+
+impl Occupancy {
+    pub fn new(width: usize, height: usize) -> Self {
+        Occupancy {
+            width,
+            height,
+            cells: vec![EMPTY; width * height],
+        }
+    }
+
+    /// Position of the given cell in `cells`, if it lies inside the world.
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            None
+        } else {
+            Some(y as usize * self.width + x as usize)
+        }
+    }
+
+    /// Position in `cells` of the cell a particle occupies, if any.
+    fn cell_of(&self, particle: &Particle) -> Option<usize> {
+        if particle.element().physics_type == PhysicsType::ENERGY {
+            return None;
+        }
+
+        let (x, y) = particle.cell();
+        self.index(x, y)
+    }
+
+    /// Whether the given cell lies inside the world.
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        self.index(x, y).is_some()
+    }
+
+    /// Index of the particle in the given cell, if any.
+    pub fn get(&self, x: i32, y: i32) -> Option<usize> {
+        self.index(x, y)
+            .map(|cell| self.cells[cell])
+            .filter(|&i| i != EMPTY)
+    }
+
+    pub fn is_occupied(&self, x: i32, y: i32) -> bool {
+        self.get(x, y).is_some()
+    }
+
+    /// Record the particle at `index` in its cell.
+    pub fn insert(&mut self, index: usize, particle: &Particle) {
+        if let Some(cell) = self.cell_of(particle) {
+            self.cells[cell] = index;
+        }
+    }
+
+    /// Forget the particle at `index`, if it is the one recorded in its cell.
+    pub fn remove(&mut self, index: usize, particle: &Particle) {
+        if let Some(cell) = self.cell_of(particle) {
+            if self.cells[cell] == index {
+                self.cells[cell] = EMPTY;
+            }
+        }
+    }
+
+    /// Swap the cells of the particles at `i` and `j`, after their positions were swapped.
+    pub fn swap(&mut self, i: usize, j: usize, particles: &[Particle]) {
+        self.remove(j, &particles[i]);
+        self.remove(i, &particles[j]);
+        self.insert(i, &particles[i]);
+        self.insert(j, &particles[j]);
+    }
+
+    /// Re-index every particle from scratch, after they all moved.
+    ///
+    /// Where several particles overlap the same cell, the last one wins.
+    pub fn rebuild(&mut self, particles: &[Particle]) {
+        self.clear();
+
+        for (i, particle) in particles.iter().enumerate() {
+            self.insert(i, particle);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.iter_mut().for_each(|cell| *cell = EMPTY);
+    }
+}
@@ -0,0 +1,525 @@
+//! Importer for saves and stamps from the original Powder Toy, in its OPS format (.cps and .stm
+//! files).
+//!
+//! An OPS file starts with a 12 byte header: `OPS1`, the version of the game that wrote it, the
+//! size of a block in pixels, the width and height of the save in blocks, and the size of the
+//! rest of the file once decompressed, as a little-endian `u32`. The rest is a bzip2 compressed
+//! BSON document. Its `partsPos` field holds the number of particles at each pixel, row by row,
+//! and `parts` holds the particles themselves in the same order.
+//!
+//! Only particles of elements we have are brought over, along with their temperature and
+//! velocity, and the edge and gravity settings. Walls, air, signs and element-specific
+//! properties have no equivalent here and are skipped.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Read};
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt};
+use bzip2::read::BzDecoder;
+use rapier2d::prelude::*;
+
+use super::{
+    backend,
+    edge::{EdgeMode, Edges},
+    gravity::{Gravity, STANDARD_GRAVITY},
+    particle::{cell_centre, Particle, ParticleVariant},
+    save::invalid,
+    world::World,
+};
+
+/// Identifies a file as an OPS save.
+const MAGIC: &[u8; 4] = b"OPS1";
+/// Size of a block in pixels, the only one the Powder Toy has ever used.
+const BLOCK_SIZE: usize = 4;
+/// Largest decompressed size accepted, so a corrupt header can't exhaust memory.
+const MAX_DATA_SIZE: u32 = 64 * 1024 * 1024;
+/// Frames per second the Powder Toy runs at, which its velocities are measured in.
+const FRAME_RATE: f32 = 60.0;
+/// Temperature a particle's one byte temperature is an offset from, in Kelvin.
+const BASE_TEMPERATURE: f32 = 294.15;
+/// Prefix of the palette identifiers of the Powder Toy's own elements.
+const DEFAULT_PREFIX: &str = "DEFAULT_PT_";
+
+/// The Powder Toy's ID and name for each element we have an equivalent of.
+///
+/// Saves with a palette name the element behind each ID, since mods can renumber them. Older
+/// saves use these IDs.
+const ELEMENT_IDS: [(u32, &str, ParticleVariant); 15] = [
+    (2, "WATR", ParticleVariant::WATR),
+    (4, "FIRE", ParticleVariant::FIRE),
+    (5, "STNE", ParticleVariant::STNE),
+    (6, "LAVA", ParticleVariant::LAVA),
+    (11, "PLEX", ParticleVariant::C4),
+    (13, "ICEI", ParticleVariant::ICE),
+    (15, "SPRK", ParticleVariant::SPRK),
+    (17, "WOOD", ParticleVariant::WOOD),
+    (18, "NEUT", ParticleVariant::NEUT),
+    (19, "PLUT", ParticleVariant::PLUT),
+    (23, "WTRV", ParticleVariant::STEM),
+    (32, "URAN", ParticleVariant::URAN),
+    (49, "PLSM", ParticleVariant::PLSM),
+    (57, "SMKE", ParticleVariant::SMKE),
+    (95, "DEUT", ParticleVariant::DEUT),
+];
+
+/// Bits of a particle's field descriptor, saying which optional fields follow its type.
+const FIELD_TEMPERATURE: u32 = 0x01;
+const FIELD_LIFE: u32 = 0x02;
+const FIELD_LIFE_HIGH: u32 = 0x04;
+const FIELD_TMP: u32 = 0x08;
+const FIELD_TMP_HIGH: u32 = 0x10;
+const FIELD_CTYPE: u32 = 0x20;
+const FIELD_DECO: u32 = 0x40;
+const FIELD_VX: u32 = 0x80;
+const FIELD_VY: u32 = 0x100;
+const FIELD_CTYPE_HIGH: u32 = 0x200;
+const FIELD_TMP2: u32 = 0x400;
+const FIELD_TMP2_HIGH: u32 = 0x800;
+const FIELD_TMP_TOP: u32 = 0x1000;
+const FIELD_TMP3_TMP4: u32 = 0x2000;
+const FIELD_TYPE_HIGH: u32 = 0x4000;
+const FIELD_DESCRIPTOR_HIGH: u32 = 0x8000;
+const FIELD_TMP3_TMP4_HIGH: u32 = 0x10000;
+
+/// What couldn't be brought over from an imported save.
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    /// Particles imported.
+    pub imported: usize,
+    /// Number of particles of each element we have no equivalent of, by Powder Toy name, or by
+    /// ID for saves without a palette.
+    pub unsupported: BTreeMap<String, usize>,
+    /// Particles dropped because another one was already in their cell.
+    pub overlapping: usize,
+}
+
+/// A BSON value, keeping only the kinds the importer reads.
+enum Value {
+    Double(f64),
+    Document(Vec<(String, Value)>),
+    Binary(Vec<u8>),
+    Bool(bool),
+    Int(i64),
+    Other,
+}
+
+/// Read an OPS save into a new world of its size, with the backend of the given name.
+pub fn read<R: Read>(reader: &mut R, backend_name: &str) -> io::Result<(World, ImportReport)> {
+    let mut header = [0; 12];
+    reader.read_exact(&mut header)?;
+
+    if header[..4] != MAGIC[..] {
+        return Err(invalid(
+            if header.starts_with(b"PSv") || header.starts_with(b"fuC") {
+                "saves from before the OPS format aren't supported"
+            } else {
+                "not a Powder Toy save"
+            },
+        ));
+    }
+
+    if header[5] as usize != BLOCK_SIZE {
+        return Err(invalid(&format!("unsupported block size {}", header[5])));
+    }
+
+    let (width, height) = (
+        header[6] as usize * BLOCK_SIZE,
+        header[7] as usize * BLOCK_SIZE,
+    );
+
+    if width == 0 || height == 0 {
+        return Err(invalid("empty save"));
+    }
+
+    let size = LittleEndian::read_u32(&header[8..]);
+
+    if size > MAX_DATA_SIZE {
+        return Err(invalid(&format!("save too large, {} bytes", size)));
+    }
+
+    let mut data = Vec::with_capacity(size as usize);
+    BzDecoder::new(reader)
+        .take(size as u64)
+        .read_to_end(&mut data)?;
+
+    let document = read_document(&mut data.as_slice())?;
+
+    let backend = backend::from_name(backend_name, width, height)
+        .ok_or_else(|| invalid(&format!("unknown backend {}", backend_name)))?;
+
+    let mut world = World::empty(backend);
+
+    apply_settings(&mut world, &document);
+
+    let report = read_particles(&mut world, &document)?;
+
+    Ok((world, report))
+}
+
+/// Copy the save's edge and gravity settings over to the world.
+fn apply_settings(world: &mut World, document: &[(String, Value)]) {
+    match field(document, "edgeMode") {
+        Some(Value::Int(0)) => world.set_edges(Edges::all(EdgeMode::Void)),
+        Some(Value::Int(2)) => world.set_edges(Edges::all(EdgeMode::Wrap)),
+        _ => {}
+    }
+
+    let custom_gravity = |key| match field(document, key) {
+        Some(Value::Double(g)) => *g as f32,
+        _ => 0.0,
+    };
+
+    match field(document, "gravityMode") {
+        Some(Value::Int(1)) => world.set_gravity(Gravity::Off),
+        Some(Value::Int(2)) => world.set_gravity(Gravity::Radial(STANDARD_GRAVITY)),
+        Some(Value::Int(3)) => world.set_gravity(Gravity::Directional(
+            vector![
+                custom_gravity("customGravityX"),
+                custom_gravity("customGravityY")
+            ] * STANDARD_GRAVITY,
+        )),
+        _ => {}
+    }
+
+    if let Some(Value::Bool(true)) = field(document, "gravityEnable") {
+        world.set_newtonian_gravity(true);
+    }
+}
+
+/// Add the save's particles to the world, counting those that couldn't be.
+fn read_particles(world: &mut World, document: &[(String, Value)]) -> io::Result<ImportReport> {
+    let mut report = ImportReport::default();
+
+    let (mut positions, mut parts) = match (field(document, "partsPos"), field(document, "parts")) {
+        (Some(Value::Binary(positions)), Some(Value::Binary(parts))) => {
+            (positions.as_slice(), parts.as_slice())
+        }
+        _ => return Ok(report),
+    };
+
+    // The element name behind each ID, if the save says
+    let mut palette = HashMap::new();
+
+    if let Some(Value::Document(entries)) = field(document, "palette") {
+        for (identifier, id) in entries {
+            if let Value::Int(id) = id {
+                let name = identifier
+                    .strip_prefix(DEFAULT_PREFIX)
+                    .unwrap_or(identifier);
+                palette.insert(*id as u32, name.to_string());
+            }
+        }
+    }
+
+    let mut rng = rand::thread_rng();
+
+    for y in 0..world.height() {
+        for x in 0..world.width() {
+            for _ in 0..positions.read_u24::<BigEndian>()? {
+                let (id, saved) = read_particle(&mut parts)?;
+
+                let known = match palette.get(&id) {
+                    Some(name) => ELEMENT_IDS.iter().find(|(_, known, _)| known == name),
+                    None => ELEMENT_IDS.iter().find(|(known, _, _)| *known == id),
+                };
+
+                let variant = match known {
+                    Some(&(_, _, variant)) => variant,
+                    None => {
+                        let name = palette.get(&id).cloned().unwrap_or_else(|| id.to_string());
+                        *report.unsupported.entry(name).or_default() += 1;
+                        continue;
+                    }
+                };
+
+                let colour = variant.element().random_colour(&mut rng);
+                let (cx, cy) = cell_centre((x as i32, y as i32));
+                let mut particle = Particle::new(cx, cy, variant, colour);
+                particle.temperature = saved.temperature;
+
+                // Energy keeps its own speed when the save doesn't say otherwise
+                if saved.vx != 0.0 || saved.vy != 0.0 {
+                    particle.vx = saved.vx;
+                    particle.vy = saved.vy;
+                }
+
+                if world.insert_particle(particle) {
+                    report.imported += 1;
+                } else {
+                    report.overlapping += 1;
+                }
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// The parts of a saved particle that carry over.
+struct SavedParticle {
+    temperature: f32,
+    /// Velocity in pixels per second.
+    vx: f32,
+    vy: f32,
+}
+
+/// Read the next particle from the `parts` field, returning its element ID and state.
+fn read_particle(parts: &mut &[u8]) -> io::Result<(u32, SavedParticle)> {
+    let mut id = parts.read_u8()? as u32;
+    let mut fields = parts.read_u16::<LittleEndian>()? as u32;
+
+    if fields & FIELD_DESCRIPTOR_HIGH != 0 {
+        fields |= (parts.read_u8()? as u32) << 16;
+    }
+
+    if fields & FIELD_TYPE_HIGH != 0 {
+        id |= (parts.read_u8()? as u32) << 8;
+    }
+
+    let temperature = if fields & FIELD_TEMPERATURE != 0 {
+        parts.read_u16::<LittleEndian>()? as f32
+    } else {
+        parts.read_i8()? as f32 + BASE_TEMPERATURE
+    };
+
+    // Fields with no equivalent here are skipped over
+    skip(parts, fields, FIELD_LIFE, 1)?;
+    skip(parts, fields, FIELD_LIFE | FIELD_LIFE_HIGH, 1)?;
+    skip(parts, fields, FIELD_TMP, 1)?;
+    skip(parts, fields, FIELD_TMP | FIELD_TMP_HIGH, 1)?;
+    skip(parts, fields, FIELD_TMP | FIELD_TMP_HIGH | FIELD_TMP_TOP, 2)?;
+    skip(parts, fields, FIELD_CTYPE, 1)?;
+    skip(parts, fields, FIELD_CTYPE | FIELD_CTYPE_HIGH, 3)?;
+    skip(parts, fields, FIELD_DECO, 4)?;
+
+    let vx = read_velocity(parts, fields, FIELD_VX)?;
+    let vy = read_velocity(parts, fields, FIELD_VY)?;
+
+    skip(parts, fields, FIELD_TMP2, 1)?;
+    skip(parts, fields, FIELD_TMP2 | FIELD_TMP2_HIGH, 1)?;
+    skip(parts, fields, FIELD_TMP3_TMP4, 4)?;
+    skip(parts, fields, FIELD_TMP3_TMP4 | FIELD_TMP3_TMP4_HIGH, 4)?;
+
+    Ok((
+        id,
+        SavedParticle {
+            temperature,
+            vx,
+            vy,
+        },
+    ))
+}
+
+/// Skip `bytes` bytes of `parts` if the field descriptor has every one of `bits` set.
+fn skip(parts: &mut &[u8], fields: u32, bits: u32, bytes: usize) -> io::Result<()> {
+    if fields & bits == bits {
+        take(parts, bytes)?;
+    }
+
+    Ok(())
+}
+
+/// Read a velocity component if the field descriptor has it, converting it to pixels per second.
+fn read_velocity(parts: &mut &[u8], fields: u32, bit: u32) -> io::Result<f32> {
+    if fields & bit == 0 {
+        return Ok(0.0);
+    }
+
+    Ok((parts.read_u8()? as f32 - 127.0) / 16.0 * FRAME_RATE)
+}
+
+/// Read a BSON document, including nested documents and arrays.
+fn read_document(data: &mut &[u8]) -> io::Result<Vec<(String, Value)>> {
+    let size = data.read_i32::<LittleEndian>()?;
+
+    if size < 5 {
+        return Err(invalid("bad document size"));
+    }
+
+    let mut body = take(data, size as usize - 4)?;
+    let mut fields = Vec::new();
+
+    loop {
+        let kind = body.read_u8()?;
+
+        if kind == 0 {
+            break;
+        }
+
+        let end = body
+            .iter()
+            .position(|&byte| byte == 0)
+            .ok_or_else(|| invalid("unterminated key"))?;
+        let key = take(&mut body, end + 1)?;
+        let key = String::from_utf8_lossy(&key[..end]).into_owned();
+
+        let value = match kind {
+            0x01 => Value::Double(body.read_f64::<LittleEndian>()?),
+            0x02 => {
+                let length = body.read_i32::<LittleEndian>()?.max(0) as usize;
+                take(&mut body, length)?;
+                Value::Other
+            }
+            0x03 | 0x04 => Value::Document(read_document(&mut body)?),
+            0x05 => {
+                let length = body.read_i32::<LittleEndian>()?.max(0) as usize;
+                let _subtype = body.read_u8()?;
+
+                Value::Binary(take(&mut body, length)?.to_vec())
+            }
+            0x08 => Value::Bool(body.read_u8()? != 0),
+            0x10 => Value::Int(body.read_i32::<LittleEndian>()? as i64),
+            0x12 => Value::Int(body.read_i64::<LittleEndian>()?),
+            0x07 => {
+                take(&mut body, 12)?;
+                Value::Other
+            }
+            0x09 | 0x11 => {
+                take(&mut body, 8)?;
+                Value::Other
+            }
+            0x0A => Value::Other,
+            _ => return Err(invalid(&format!("unsupported BSON type {}", kind))),
+        };
+
+        fields.push((key, value));
+    }
+
+    Ok(fields)
+}
+
+/// The value of a document's field, if it has one.
+fn field<'a>(document: &'a [(String, Value)], key: &str) -> Option<&'a Value> {
+    document
+        .iter()
+        .find(|(name, _)| name == key)
+        .map(|(_, value)| value)
+}
+
+/// Split the next `length` bytes off the front of `data`.
+fn take<'a>(data: &mut &'a [u8], length: usize) -> io::Result<&'a [u8]> {
+    if length > data.len() {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+
+    let (taken, rest) = data.split_at(length);
+    *data = rest;
+
+    Ok(taken)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use bzip2::{write::BzEncoder, Compression};
+
+    use super::*;
+
+    /// A BSON document holding the given encoded elements.
+    fn document(elements: &[Vec<u8>]) -> Vec<u8> {
+        let body = elements.concat();
+        let mut document = ((body.len() + 5) as i32).to_le_bytes().to_vec();
+        document.extend_from_slice(&body);
+        document.push(0);
+        document
+    }
+
+    /// A BSON element of the given type and key, followed by its encoded value.
+    fn element(kind: u8, key: &str, value: &[u8]) -> Vec<u8> {
+        [&[kind], key.as_bytes(), &[0], value].concat()
+    }
+
+    fn binary(key: &str, data: &[u8]) -> Vec<u8> {
+        let value = [&(data.len() as i32).to_le_bytes()[..], &[0], data].concat();
+        element(0x05, key, &value)
+    }
+
+    /// An 8x4 save with wrapping edges, radial and Newtonian gravity, and a few particles.
+    fn fixture() -> Vec<u8> {
+        let (width, height) = (8, 4);
+        let mut counts = vec![0u32; width * height];
+
+        // Water at 350 K with a stone on top of it in the same cell
+        counts[width + 1] = 2;
+        // Uranium under a palette ID of 200, moving right and up
+        counts[width + 2] = 1;
+        // Dust, which has no equivalent
+        counts[width + 3] = 1;
+        // Plastic explosive, which becomes C4
+        counts[2 * width] = 1;
+
+        let parts = [
+            &[2, 0x01, 0x00, 0x5E, 0x01][..],
+            &[5, 0x00, 0x00, 0][..],
+            &[200, 0x80, 0x01, 0, 127 + 16, 127 - 16][..],
+            &[1, 0x00, 0x00, 0][..],
+            &[11, 0x00, 0x00, 0][..],
+        ]
+        .concat();
+
+        let positions: Vec<u8> = counts
+            .iter()
+            .flat_map(|count| count.to_be_bytes()[1..].to_vec())
+            .collect();
+
+        let palette = document(&[
+            element(0x10, "DEFAULT_PT_URAN", &200i32.to_le_bytes()),
+            element(0x10, "DEFAULT_PT_DUST", &1i32.to_le_bytes()),
+        ]);
+
+        let data = document(&[
+            element(0x10, "edgeMode", &2i32.to_le_bytes()),
+            element(0x10, "gravityMode", &2i32.to_le_bytes()),
+            element(0x08, "gravityEnable", &[1]),
+            element(0x02, "author", &[&3i32.to_le_bytes()[..], b"me\0"].concat()),
+            element(0x03, "palette", &palette),
+            binary("partsPos", &positions),
+            binary("parts", &parts),
+        ]);
+
+        let mut save = b"OPS1".to_vec();
+        save.extend_from_slice(&[97, BLOCK_SIZE as u8, 2, 1]);
+        save.extend_from_slice(&(data.len() as u32).to_le_bytes());
+
+        let mut encoder = BzEncoder::new(save, Compression::default());
+        encoder.write_all(&data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn imports_settings_and_particles() {
+        let (world, report) = read(&mut fixture().as_slice(), "grid").unwrap();
+
+        assert_eq!((world.width(), world.height()), (8, 4));
+        assert_eq!(world.edges(), Edges::all(EdgeMode::Wrap));
+        assert_eq!(world.gravity(), Gravity::Radial(STANDARD_GRAVITY));
+        assert!(world.newtonian_gravity());
+
+        assert_eq!(report.imported, 3);
+        assert_eq!(report.overlapping, 1);
+        assert_eq!(report.unsupported.get("DUST"), Some(&1));
+
+        let water = world.particle_at(1, 1).unwrap();
+        assert_eq!(water.variant(), ParticleVariant::WATR);
+        assert_eq!(water.temperature, 350.0);
+
+        let uranium = world.particle_at(2, 1).unwrap();
+        assert_eq!(uranium.variant(), ParticleVariant::URAN);
+        assert_eq!((uranium.vx, uranium.vy), (FRAME_RATE, -FRAME_RATE));
+
+        let explosive = world.particle_at(0, 2).unwrap();
+        assert_eq!(explosive.variant(), ParticleVariant::C4);
+        assert_eq!(explosive.temperature, BASE_TEMPERATURE);
+
+        assert!(world.particle_at(3, 1).is_none());
+    }
+
+    #[test]
+    fn rejects_older_formats() {
+        let error = read(&mut &b"PSv\0\0\0\0\0\0\0\0\0"[..], "grid")
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
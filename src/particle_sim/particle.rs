@@ -1,12 +1,40 @@
+use rand::Rng;
 use rapier2d::prelude::*;
 
-#[derive(Clone, Debug)]
+use super::element::Element;
+
+/// Speed energy particles travel at, in pixels per second.
+const ENERGY_SPEED: f32 = 60.0;
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum PhysicsType {
     DYNAMIC,
     STATIC,
+    /// Flows sideways and settles into layers by density.
+    LIQUID,
+    /// Rises and spreads out to fill its container.
+    GAS,
+    /// Travels in a straight line, ignoring gravity and passing through other particles.
+    ENERGY,
+}
+
+impl PhysicsType {
+    /// Whether particles of this type are simulated as rapier dynamic bodies.
+    pub fn is_dynamic(self) -> bool {
+        matches!(
+            self,
+            PhysicsType::DYNAMIC | PhysicsType::LIQUID | PhysicsType::GAS
+        )
+    }
+
+    /// Whether particles of this type flow around each other.
+    pub fn is_fluid(self) -> bool {
+        matches!(self, PhysicsType::LIQUID | PhysicsType::GAS)
+    }
 }
 
-#[derive(Clone, Debug, Copy)]
+#[derive(Clone, Debug, Copy, PartialEq, Eq, Hash)]
 pub enum ParticleVariant {
     WOOD,
     STNE,
@@ -14,87 +42,88 @@ pub enum ParticleVariant {
     PLUT,
     DEUT,
     C4,
+    WATR,
+    ICE,
+    STEM,
+    LAVA,
+    FIRE,
+    SMKE,
+    ASH,
+    SPRK,
+    NEUT,
+    PLSM,
+    FAN,
 }
 
 #[derive(Clone)]
 pub struct Particle {
     pub x: f32,
     pub y: f32,
-    physics_type: PhysicsType,
+    /// Velocity in pixels per second.
+    pub vx: f32,
+    pub vy: f32,
     variant: ParticleVariant,
     pub color: [u8; 4],
-    pub rigid_body: RigidBody,
-    pub collider: Collider,
-    pub body_handle: RigidBodyHandle,
+    /// Temperature in Kelvin.
+    pub temperature: f32,
+    /// Steps left until a short-lived particle decays, or a burning one burns out.
+    pub life: i32,
+    pub burning: bool,
+    /// Extra particles of the same element packed into this one by pressure.
+    pub compression: i32,
 }
 
 impl Particle {
-    pub fn new(
-        x: f32,
-        y: f32,
-        variant: ParticleVariant,
-        color: [u8; 4],
-        collider_set: &mut ColliderSet,
-        rigid_body_set: &mut RigidBodySet,
-    ) -> Self {
-        let physics_type: PhysicsType = match variant {
-            ParticleVariant::C4 => PhysicsType::STATIC,
-            ParticleVariant::DEUT => PhysicsType::DYNAMIC,
-            ParticleVariant::PLUT => PhysicsType::DYNAMIC,
-            ParticleVariant::STNE => PhysicsType::STATIC,
-            ParticleVariant::URAN => PhysicsType::DYNAMIC,
-            ParticleVariant::WOOD => PhysicsType::STATIC,
-        };
+    pub fn new(x: f32, y: f32, variant: ParticleVariant, color: [u8; 4]) -> Self {
+        let element = variant.element();
 
-        let collider: Collider = ColliderBuilder::cuboid(0.5, 0.5)
-            //.active_events(ActiveEvents::COLLISION_EVENTS)
-            .mass(1.0)
-            .restitution(0.0)
-            .build();
-
-        let rigid_body: RigidBody = match physics_type {
-            PhysicsType::DYNAMIC => RigidBodyBuilder::dynamic()
-                .lock_rotations()
-                .translation(vector![x as f32, y as f32])
-                .build(),
-            PhysicsType::STATIC => RigidBodyBuilder::fixed()
-                .lock_rotations()
-                .translation(vector![x as f32, y as f32])
-                .build(),
+        let (vx, vy) = if element.physics_type == PhysicsType::ENERGY {
+            let velocity = random_direction() * ENERGY_SPEED;
+            (velocity.x, velocity.y)
+        } else {
+            (0.0, 0.0)
         };
 
-        let body_handle: RigidBodyHandle = rigid_body_set.insert(rigid_body.clone());
-        collider_set.insert_with_parent(collider.clone(), body_handle, rigid_body_set);
-
         Particle {
             x,
             y,
-            physics_type,
+            vx,
+            vy,
             variant,
             color,
-            rigid_body,
-            collider,
-            body_handle,
+            temperature: element.default_temperature,
+            life: element.life,
+            burning: false,
+            compression: 0,
         }
     }
 
-    pub fn update(&mut self, rigid_body_set: &mut RigidBodySet) {
-        let particle_body = &mut rigid_body_set[self.body_handle];
-
-        self.x = particle_body.translation().x;
-        self.y = particle_body.translation().y;
-
-        if (particle_body.linvel().x.abs() < 0.01) && (particle_body.linvel().y.abs() == 0.01) {
-            /*rigid_body_set.remove(
-                self.body_handle,
-                self.island_manager,
-                self.collider_set,
-                self.impulse_joint_set,
-                self.multibody_joint_set,
-                false,
-            );*/
-            particle_body.reset_forces(false);
-            particle_body.sleep();
-        }
+    pub fn variant(&self) -> ParticleVariant {
+        self.variant
     }
+
+    /// This particle's entry in the element registry.
+    pub fn element(&self) -> &'static Element {
+        self.variant.element()
+    }
+
+    /// The integer cell this particle is drawn in.
+    pub fn cell(&self) -> (i32, i32) {
+        (self.x.floor() as i32, self.y.floor() as i32)
+    }
+}
+
+/// The centre of a cell, where new particles are placed.
+///
+/// A rapier body resting at a cell's centre can jitter slightly without crossing into the
+/// neighbouring cell, which it would if it sat on the boundary between the two.
+pub fn cell_centre((x, y): (i32, i32)) -> (f32, f32) {
+    (x as f32 + 0.5, y as f32 + 0.5)
+}
+
+/// A random unit vector.
+pub fn random_direction() -> Vector<Real> {
+    let angle = rand::thread_rng().gen_range(0.0..std::f32::consts::TAU);
+
+    vector![angle.cos(), angle.sin()]
 }
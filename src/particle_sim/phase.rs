@@ -0,0 +1,34 @@
+//! Temperature driven phase transitions between elements.
+
+use super::{
+    particle::{Particle, ParticleVariant},
+    reaction::Reaction,
+};
+
+/// Change every particle that has crossed one of its element's transition temperatures.
+pub fn transition_all(particles: &[Particle], reactions: &mut Vec<Reaction>) {
+    for (i, particle) in particles.iter().enumerate() {
+        if let Some(variant) = transition(particle) {
+            reactions.push(Reaction::Change(i, variant));
+        }
+    }
+}
+
+/// The element a particle should turn into at its current temperature, if any.
+pub fn transition(particle: &Particle) -> Option<ParticleVariant> {
+    let element = particle.element();
+
+    if let Some(low) = &element.low_transition {
+        if particle.temperature < low.temperature {
+            return Some(low.into);
+        }
+    }
+
+    if let Some(high) = &element.high_transition {
+        if particle.temperature > high.temperature {
+            return Some(high.into);
+        }
+    }
+
+    None
+}
@@ -0,0 +1,91 @@
+//! Neutron emission, absorption, scattering and fission.
+
+use rand::Rng;
+
+use super::{
+    air::AirGrid,
+    occupancy::Occupancy,
+    particle::{Particle, ParticleVariant, PhysicsType},
+    reaction::Reaction,
+};
+
+/// Steps a new neutron ignores the particle it was emitted from.
+const GRACE_STEPS: i32 = 2;
+
+/// Emit neutrons from radioactive particles and resolve neutrons passing through matter.
+///
+/// A neutron in an occupied cell may be absorbed, heating the particle and possibly splitting
+/// it into more neutrons, or scattered in a new direction. The heat of particles used up by
+/// fission goes into the air instead. Neutrons that leave the world are removed.
+pub fn irradiate(
+    particles: &mut [Particle],
+    cells: &Occupancy,
+    air: &mut AirGrid,
+    reactions: &mut Vec<Reaction>,
+) {
+    let mut rng = rand::thread_rng();
+
+    for i in 0..particles.len() {
+        if let Some(radioactivity) = &particles[i].element().radioactivity {
+            if rng.gen_range(0..10000) < radioactivity.emission_chance {
+                reactions.push(Reaction::Spawn(
+                    particles[i].x,
+                    particles[i].y,
+                    ParticleVariant::NEUT,
+                ));
+            }
+        }
+
+        if particles[i].element().physics_type != PhysicsType::ENERGY {
+            continue;
+        }
+
+        let (x, y) = particles[i].cell();
+
+        if !cells.contains(x, y) {
+            reactions.push(Reaction::Remove(i));
+            continue;
+        }
+
+        if particles[i].life > particles[i].element().life - GRACE_STEPS {
+            continue;
+        }
+
+        let j = match cells.get(x, y) {
+            Some(j) => j,
+            None => continue,
+        };
+
+        let target = particles[j].element();
+
+        if rng.gen_range(0..100) < target.neutron_absorption {
+            reactions.push(Reaction::Remove(i));
+
+            if let Some(radioactivity) = &target.radioactivity {
+                let fission = rng.gen_range(0..100) < radioactivity.fission_chance;
+
+                if fission && radioactivity.consumed {
+                    air.add_heat(particles[j].x, particles[j].y, radioactivity.heat);
+                    reactions.push(Reaction::Remove(j));
+                } else {
+                    particles[j].temperature += radioactivity.heat;
+                }
+
+                if fission {
+                    let neutrons =
+                        radioactivity.fission_neutrons as i32 * (1 + particles[j].compression);
+
+                    for _ in 0..neutrons {
+                        reactions.push(Reaction::Spawn(
+                            particles[j].x,
+                            particles[j].y,
+                            ParticleVariant::NEUT,
+                        ));
+                    }
+                }
+            }
+        } else if rng.gen_range(0..100) < target.neutron_scattering {
+            reactions.push(Reaction::Scatter(i));
+        }
+    }
+}
@@ -0,0 +1,345 @@
+//! Backend simulating every particle as a rapier rigid body.
+
+use rapier2d::prelude::*;
+
+use super::{
+    backend::Backend,
+    edge::{EdgeMode, Edges},
+    gravity::GravityField,
+    particle::{Particle, PhysicsType},
+};
+
+/// Gravity scale of gases, negative so they rise.
+const GAS_GRAVITY_SCALE: f32 = -0.2;
+/// Linear damping of gases, so they drift rather than shoot upwards.
+const GAS_DAMPING: f32 = 2.0;
+/// Linear damping of liquids, so sideways flow settles instead of building up speed.
+const LIQUID_DAMPING: f32 = 0.5;
+/// Half the thickness of the walls at solid edges, thick enough that fast particles don't
+/// tunnel through.
+const WALL_HALF_THICKNESS: f32 = 4.0;
+
+pub struct RapierBackend {
+    width: f32,
+    height: f32,
+    particles: Vec<Particle>,
+    /// The rigid body simulating each particle, in the same order as `particles`.
+    bodies: Vec<RigidBodyHandle>,
+    /// Colliders of the walls at the solid edges.
+    walls: Vec<ColliderHandle>,
+    rigid_body_set: RigidBodySet,
+    collider_set: ColliderSet,
+    integration_parameters: IntegrationParameters,
+    physics_pipeline: PhysicsPipeline,
+    island_manager: IslandManager,
+    broad_phase: BroadPhase,
+    narrow_phase: NarrowPhase,
+    impulse_joint_set: ImpulseJointSet,
+    multibody_joint_set: MultibodyJointSet,
+    ccd_solver: CCDSolver,
+    physics_hooks: (),
+    event_handler: (),
+}
+
+impl RapierBackend {
+    /// Create a rapier world walled in on the left, right and bottom.
+    pub fn new(width: usize, height: usize) -> Self {
+        let broad_phase: BroadPhase = BroadPhase::new();
+        let ccd_solver: CCDSolver = CCDSolver::new();
+        let collider_set: ColliderSet = ColliderSet::new();
+        let impulse_joint_set: ImpulseJointSet = ImpulseJointSet::new();
+        let island_manager: IslandManager = IslandManager::new();
+        let multibody_joint_set: MultibodyJointSet = MultibodyJointSet::new();
+        let narrow_phase: NarrowPhase = NarrowPhase::new();
+        let physics_pipeline: PhysicsPipeline = PhysicsPipeline::new();
+        let rigid_body_set: RigidBodySet = RigidBodySet::new();
+
+        /*let (collision_send, collision_recv) = crossbeam::channel::unbounded();
+        let (contact_force_send, contact_force_recv) = crossbeam::channel::unbounded();
+        let event_handler = ChannelEventCollector::new(collision_send, contact_force_send);*/
+
+        let mut backend = RapierBackend {
+            width: width as f32,
+            height: height as f32,
+            particles: Vec::new(),
+            bodies: Vec::new(),
+            walls: Vec::new(),
+            rigid_body_set,
+            collider_set,
+            integration_parameters: IntegrationParameters::default(),
+            physics_pipeline,
+            island_manager,
+            broad_phase,
+            narrow_phase,
+            impulse_joint_set,
+            multibody_joint_set,
+            ccd_solver,
+            physics_hooks: (),
+            event_handler: (),
+        };
+
+        backend.set_edges(Edges::default());
+
+        backend
+    }
+
+    /// Create a rigid body and collider to simulate a particle, starting at its current position
+    /// and velocity.
+    fn attach_body(&mut self, particle: &Particle) -> RigidBodyHandle {
+        let element = particle.element();
+        let (x, y) = (particle.x, particle.y);
+
+        // Fluids are round and frictionless so they slide off each other
+        let collider: Collider = if element.physics_type.is_fluid() {
+            ColliderBuilder::ball(0.5).friction(0.0)
+        } else {
+            ColliderBuilder::cuboid(0.5, 0.5)
+        }
+        //.active_events(ActiveEvents::COLLISION_EVENTS)
+        .mass(element.density)
+        .restitution(0.0)
+        .sensor(element.physics_type == PhysicsType::ENERGY)
+        .build();
+
+        let rigid_body: RigidBody = match element.physics_type {
+            PhysicsType::DYNAMIC => RigidBodyBuilder::dynamic()
+                .lock_rotations()
+                .translation(vector![x, y])
+                .build(),
+            PhysicsType::LIQUID => RigidBodyBuilder::dynamic()
+                .lock_rotations()
+                .translation(vector![x, y])
+                .linear_damping(LIQUID_DAMPING)
+                .build(),
+            PhysicsType::GAS => RigidBodyBuilder::dynamic()
+                .lock_rotations()
+                .translation(vector![x, y])
+                .gravity_scale(GAS_GRAVITY_SCALE)
+                .linear_damping(GAS_DAMPING)
+                .build(),
+            PhysicsType::STATIC => RigidBodyBuilder::fixed()
+                .lock_rotations()
+                .translation(vector![x, y])
+                .build(),
+            PhysicsType::ENERGY => RigidBodyBuilder::kinematic_velocity_based()
+                .lock_rotations()
+                .translation(vector![x, y])
+                .build(),
+        };
+
+        let body_handle: RigidBodyHandle = self.rigid_body_set.insert(rigid_body);
+        self.collider_set
+            .insert_with_parent(collider, body_handle, &mut self.rigid_body_set);
+
+        self.rigid_body_set[body_handle].set_linvel(vector![particle.vx, particle.vy], true);
+
+        body_handle
+    }
+
+    /// Remove a particle's rigid body along with its collider, any joints and its place in the
+    /// simulation islands.
+    fn detach_body(&mut self, handle: RigidBodyHandle) {
+        self.rigid_body_set.remove(
+            handle,
+            &mut self.island_manager,
+            &mut self.collider_set,
+            &mut self.impulse_joint_set,
+            &mut self.multibody_joint_set,
+            true,
+        );
+    }
+
+    /// Copy a particle's position and velocity back from its rigid body.
+    fn sync(particle: &mut Particle, particle_body: &mut RigidBody) {
+        // Fixed bodies never move, so there is nothing to sync
+        if particle.element().physics_type == PhysicsType::STATIC {
+            return;
+        }
+
+        particle.x = particle_body.translation().x;
+        particle.y = particle_body.translation().y;
+        particle.vx = particle_body.linvel().x;
+        particle.vy = particle_body.linvel().y;
+
+        if (particle_body.linvel().x.abs() < 0.01) && (particle_body.linvel().y.abs() == 0.01) {
+            particle_body.reset_forces(false);
+            particle_body.sleep();
+        }
+    }
+}
+
+impl Backend for RapierBackend {
+    fn name(&self) -> &'static str {
+        "rapier"
+    }
+
+    fn size(&self) -> (usize, usize) {
+        (self.width as usize, self.height as usize)
+    }
+
+    fn step(&mut self, dt: f32, gravity: &GravityField) {
+        self.integration_parameters.dt = dt;
+
+        // Rapier only knows uniform gravity, so anything else is applied to each body by hand
+        let uniform = match gravity.uniform() {
+            Some(g) => g,
+            None => {
+                for (particle, &handle) in self.particles.iter().zip(&self.bodies) {
+                    let body = &mut self.rigid_body_set[handle];
+
+                    if body.is_dynamic() {
+                        let acceleration = gravity.at(particle.x, particle.y);
+                        let linvel = body.linvel() + acceleration * body.gravity_scale() * dt;
+
+                        body.set_linvel(linvel, true);
+                    }
+                }
+
+                Vector::zeros()
+            }
+        };
+
+        self.physics_pipeline.step(
+            &uniform,
+            &self.integration_parameters,
+            &mut self.island_manager,
+            &mut self.broad_phase,
+            &mut self.narrow_phase,
+            &mut self.rigid_body_set,
+            &mut self.collider_set,
+            &mut self.impulse_joint_set,
+            &mut self.multibody_joint_set,
+            &mut self.ccd_solver,
+            &self.physics_hooks,
+            &self.event_handler,
+        );
+
+        for (particle, &handle) in self.particles.iter_mut().zip(&self.bodies) {
+            Self::sync(particle, &mut self.rigid_body_set[handle]);
+        }
+    }
+
+    fn add(&mut self, particle: Particle) -> bool {
+        let handle = self.attach_body(&particle);
+        self.particles.push(particle);
+        self.bodies.push(handle);
+
+        true
+    }
+
+    fn remove(&mut self, index: usize) -> Particle {
+        let handle = self.bodies.swap_remove(index);
+        self.detach_body(handle);
+
+        self.particles.swap_remove(index)
+    }
+
+    fn replace(&mut self, index: usize, particle: Particle) {
+        let handle = self.attach_body(&particle);
+        let previous = std::mem::replace(&mut self.bodies[index], handle);
+        self.detach_body(previous);
+
+        self.particles[index] = particle;
+    }
+
+    fn particles(&self) -> &[Particle] {
+        &self.particles
+    }
+
+    fn particles_mut(&mut self) -> &mut [Particle] {
+        &mut self.particles
+    }
+
+    fn set_edges(&mut self, edges: Edges) {
+        for handle in self.walls.drain(..) {
+            self.collider_set.remove(
+                handle,
+                &mut self.island_manager,
+                &mut self.rigid_body_set,
+                true,
+            );
+        }
+
+        let (width, height) = (self.width, self.height);
+        let thickness = WALL_HALF_THICKNESS;
+
+        // Each wall's inner face lies on its edge, and the walls overlap at the corners
+        let sides = [
+            (
+                edges.left,
+                -thickness,
+                height / 2.0,
+                thickness,
+                height / 2.0 + 2.0 * thickness,
+            ),
+            (
+                edges.right,
+                width + thickness,
+                height / 2.0,
+                thickness,
+                height / 2.0 + 2.0 * thickness,
+            ),
+            (
+                edges.top,
+                width / 2.0,
+                -thickness,
+                width / 2.0 + 2.0 * thickness,
+                thickness,
+            ),
+            (
+                edges.bottom,
+                width / 2.0,
+                height + thickness,
+                width / 2.0 + 2.0 * thickness,
+                thickness,
+            ),
+        ];
+
+        for (mode, x, y, half_width, half_height) in sides {
+            if mode == EdgeMode::Solid {
+                let wall = ColliderBuilder::cuboid(half_width, half_height)
+                    .translation(vector![x, y])
+                    .build();
+
+                self.walls.push(self.collider_set.insert(wall));
+            }
+        }
+    }
+
+    fn apply_impulse(&mut self, index: usize, impulse: Vector<Real>) {
+        self.rigid_body_set[self.bodies[index]].apply_impulse(impulse, true);
+    }
+
+    fn set_velocity(&mut self, index: usize, velocity: Vector<Real>) {
+        let particle = &mut self.particles[index];
+
+        particle.vx = velocity.x;
+        particle.vy = velocity.y;
+
+        self.rigid_body_set[self.bodies[index]].set_linvel(velocity, true);
+    }
+
+    fn set_position(&mut self, index: usize, x: f32, y: f32) {
+        let particle = &mut self.particles[index];
+
+        particle.x = x;
+        particle.y = y;
+
+        self.rigid_body_set[self.bodies[index]].set_translation(vector![x, y], true);
+    }
+
+    fn swap(&mut self, i: usize, j: usize) {
+        let (a, b) = (self.bodies[i], self.bodies[j]);
+        let translation_a = *self.rigid_body_set[a].translation();
+        let translation_b = *self.rigid_body_set[b].translation();
+
+        self.rigid_body_set[a].set_translation(translation_b, true);
+        self.rigid_body_set[b].set_translation(translation_a, true);
+
+        let (x, y) = (self.particles[i].x, self.particles[i].y);
+        self.particles[i].x = self.particles[j].x;
+        self.particles[i].y = self.particles[j].y;
+        self.particles[j].x = x;
+        self.particles[j].y = y;
+    }
+}
@@ -0,0 +1,45 @@
+//! Changes to the particle list requested by element behaviours during a step.
+
+use super::particle::{Particle, ParticleVariant};
+
+/// A change requested by an element behaviour while the particles are being iterated.
+///
+/// Reactions are applied together once every behaviour has run, so particle indices stay
+/// valid until then.
+#[derive(Clone, Copy, Debug)]
+pub enum Reaction {
+    /// Turn the particle at this index into another element.
+    Change(usize, ParticleVariant),
+    /// Remove the particle at this index.
+    Remove(usize),
+    /// Add a new particle at this position.
+    Spawn(f32, f32, ParticleVariant),
+    /// Detonate the explosive particle at this index.
+    Explode(usize),
+    /// Send the energy particle at this index off in a random direction.
+    Scatter(usize),
+    /// Change the velocity of the particle at this index by this much, in pixels per second.
+    Push(usize, f32, f32),
+    /// Swap the positions of the particles at these indices.
+    Swap(usize, usize),
+}
+
+/// Count down the life of short-lived particles, decaying them once it runs out.
+pub fn age(particles: &mut [Particle], reactions: &mut Vec<Reaction>) {
+    for (i, particle) in particles.iter_mut().enumerate() {
+        let element = particle.element();
+
+        if element.life == 0 {
+            continue;
+        }
+
+        particle.life -= 1;
+
+        if particle.life <= 0 {
+            reactions.push(match element.decays_into {
+                Some(variant) => Reaction::Change(i, variant),
+                None => Reaction::Remove(i),
+            });
+        }
+    }
+}
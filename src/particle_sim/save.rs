@@ -0,0 +1,279 @@
+//! Versioned binary save format for worlds.
+//!
+//! All numbers are little-endian. A save is laid out as:
+//!
+//! - `MAGIC`, then the format version as a `u16`
+//! - width and height in cells, as `u32`s
+//! - the backend name, as a `u8` length followed by that many bytes
+//! - the tick rate as an `f32`
+//! - the left, right, top and bottom edge modes, a `u8` each
+//! - the gravity mode as a `u8`, followed by two `f32`s of its direction or strength, and
+//!   whether Newtonian gravity is on as a `u8`
+//! - the number of particles as a `u32`, then for each particle its element's code as a `u8`
+//!   length followed by that many bytes, position, velocity and temperature as `f32`s, life as an
+//!   `i32`, whether it's burning as a `u8`, compression as an `i32` and its RGBA colour
+//!
+//! Elements are stored by code rather than by their place in `ELEMENTS`, so elements can be
+//! added or reordered without breaking existing saves.
+//!
+//! Saves from older versions keep loading when the format changes, by bumping `VERSION` and
+//! branching on the version read.
+
+use std::io::{self, Read, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use rapier2d::prelude::*;
+
+use super::{
+    backend,
+    edge::{EdgeMode, Edges},
+    element::ELEMENTS,
+    gravity::Gravity,
+    particle::Particle,
+    world::{World, MAX_SIZE},
+};
+
+/// Identifies a file as a world save.
+const MAGIC: &[u8; 4] = b"PTRS";
+/// Version written by `write`, and the newest `read` understands.
+const VERSION: u16 = 1;
+
+/// Write a world's size, settings and particles.
+pub fn write<W: Write>(world: &World, writer: &mut W) -> io::Result<()> {
+    writer.write_all(MAGIC)?;
+    writer.write_u16::<LittleEndian>(VERSION)?;
+
+    writer.write_u32::<LittleEndian>(world.width() as u32)?;
+    writer.write_u32::<LittleEndian>(world.height() as u32)?;
+
+    write_str(writer, world.backend_name())?;
+
+    writer.write_f32::<LittleEndian>(world.tick_rate())?;
+
+    let edges = world.edges();
+
+    for mode in [edges.left, edges.right, edges.top, edges.bottom] {
+        writer.write_u8(mode as u8)?;
+    }
+
+    let (tag, x, y) = match world.gravity() {
+        Gravity::Directional(g) => (0, g.x, g.y),
+        Gravity::Off => (1, 0.0, 0.0),
+        Gravity::Radial(strength) => (2, strength, 0.0),
+    };
+
+    writer.write_u8(tag)?;
+    writer.write_f32::<LittleEndian>(x)?;
+    writer.write_f32::<LittleEndian>(y)?;
+    writer.write_u8(world.newtonian_gravity() as u8)?;
+
+    writer.write_u32::<LittleEndian>(world.particles().len() as u32)?;
+
+    for particle in world.particles() {
+        write_str(writer, particle.element().code)?;
+
+        for value in [
+            particle.x,
+            particle.y,
+            particle.vx,
+            particle.vy,
+            particle.temperature,
+        ] {
+            writer.write_f32::<LittleEndian>(value)?;
+        }
+
+        writer.write_i32::<LittleEndian>(particle.life)?;
+        writer.write_u8(particle.burning as u8)?;
+        writer.write_i32::<LittleEndian>(particle.compression)?;
+        writer.write_all(&particle.color)?;
+    }
+
+    Ok(())
+}
+
+/// Read a world written by `write`, with a fresh backend of the kind it was saved with.
+pub fn read<R: Read>(reader: &mut R) -> io::Result<World> {
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+
+    if &magic != MAGIC {
+        return Err(invalid("not a world save"));
+    }
+
+    let version = reader.read_u16::<LittleEndian>()?;
+
+    if version != VERSION {
+        return Err(invalid(&format!("unsupported save version {}", version)));
+    }
+
+    let width = reader.read_u32::<LittleEndian>()?;
+    let height = reader.read_u32::<LittleEndian>()?;
+
+    if width == 0 || height == 0 || width > MAX_SIZE as u32 || height > MAX_SIZE as u32 {
+        return Err(invalid(&format!("bad world size {}x{}", width, height)));
+    }
+
+    let (width, height) = (width as usize, height as usize);
+
+    let backend_name = read_string(reader)?;
+    let backend = backend::from_name(&backend_name, width, height)
+        .ok_or_else(|| invalid(&format!("unknown backend {}", backend_name)))?;
+
+    let mut world = World::empty(backend);
+
+    let tick_rate = reader.read_f32::<LittleEndian>()?;
+
+    if tick_rate.is_nan() || tick_rate <= 0.0 {
+        return Err(invalid(&format!("bad tick rate {}", tick_rate)));
+    }
+
+    world.set_tick_rate(tick_rate);
+
+    world.set_edges(Edges {
+        left: read_edge_mode(reader)?,
+        right: read_edge_mode(reader)?,
+        top: read_edge_mode(reader)?,
+        bottom: read_edge_mode(reader)?,
+    });
+
+    let tag = reader.read_u8()?;
+    let x = reader.read_f32::<LittleEndian>()?;
+    let y = reader.read_f32::<LittleEndian>()?;
+
+    if !x.is_finite() || !y.is_finite() {
+        return Err(invalid(&format!("bad gravity {},{}", x, y)));
+    }
+
+    world.set_gravity(match tag {
+        0 => Gravity::Directional(vector![x, y]),
+        1 => Gravity::Off,
+        2 => Gravity::Radial(x),
+        _ => return Err(invalid(&format!("unknown gravity mode {}", tag))),
+    });
+
+    world.set_newtonian_gravity(reader.read_u8()? != 0);
+
+    let count = reader.read_u32::<LittleEndian>()?;
+    let mut particles = Vec::new();
+
+    for _ in 0..count {
+        let code = read_string(reader)?;
+        let element = ELEMENTS
+            .iter()
+            .find(|element| element.code == code)
+            .ok_or_else(|| invalid(&format!("unknown element {}", code)))?;
+
+        let mut values = [0.0; 5];
+        reader.read_f32_into::<LittleEndian>(&mut values)?;
+        let [x, y, vx, vy, temperature] = values;
+
+        let life = reader.read_i32::<LittleEndian>()?;
+        let burning = reader.read_u8()? != 0;
+        let compression = reader.read_i32::<LittleEndian>()?;
+
+        let mut colour = [0; 4];
+        reader.read_exact(&mut colour)?;
+
+        let mut particle = Particle::new(x, y, element.variant, colour);
+        particle.vx = vx;
+        particle.vy = vy;
+        particle.temperature = temperature;
+        particle.life = life;
+        particle.burning = burning;
+        particle.compression = compression;
+
+        particles.push(particle);
+    }
+
+    world.restore_particles(particles);
+
+    Ok(world)
+}
+
+/// Write a string of at most 255 bytes, prefixed with its length.
+fn write_str<W: Write>(writer: &mut W, string: &str) -> io::Result<()> {
+    writer.write_u8(string.len() as u8)?;
+    writer.write_all(string.as_bytes())
+}
+
+/// Read a string written by `write_str`.
+fn read_string<R: Read>(reader: &mut R) -> io::Result<String> {
+    let mut bytes = vec![0; reader.read_u8()? as usize];
+    reader.read_exact(&mut bytes)?;
+
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+fn read_edge_mode<R: Read>(reader: &mut R) -> io::Result<EdgeMode> {
+    match reader.read_u8()? {
+        0 => Ok(EdgeMode::Solid),
+        1 => Ok(EdgeMode::Void),
+        2 => Ok(EdgeMode::Wrap),
+        mode => Err(invalid(&format!("unknown edge mode {}", mode))),
+    }
+}
+
+/// An error for a save that can't be read, of either this format or one being imported.
+pub fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::particle_sim::particle::ParticleVariant;
+
+    #[test]
+    fn round_trip_keeps_settings_and_particles() {
+        let mut world = World::empty(backend::from_name("rapier", 40, 30).unwrap());
+        world.set_tick_rate(30.0);
+        world.set_edges(Edges {
+            left: EdgeMode::Wrap,
+            right: EdgeMode::Wrap,
+            top: EdgeMode::Void,
+            bottom: EdgeMode::Solid,
+        });
+        world.set_gravity(Gravity::Radial(4.0));
+        world.set_newtonian_gravity(true);
+
+        let mut hot = Particle::new(10.5, 12.5, ParticleVariant::WATR, [1, 2, 3, 255]);
+        hot.vx = 3.0;
+        hot.vy = -2.0;
+        hot.temperature = 350.0;
+        hot.life = 7;
+        hot.burning = true;
+        hot.compression = 2;
+
+        // Bodies settling against each other can share a cell, and must both survive
+        let settling = Particle::new(10.9, 12.2, ParticleVariant::STNE, [4, 5, 6, 255]);
+        world.restore_particles([hot, settling]);
+
+        let mut bytes = Vec::new();
+        write(&world, &mut bytes).unwrap();
+        let loaded = read(&mut bytes.as_slice()).unwrap();
+
+        assert_eq!((loaded.width(), loaded.height()), (40, 30));
+        assert_eq!(loaded.backend_name(), "rapier");
+        assert_eq!(loaded.tick_rate(), world.tick_rate());
+        assert_eq!(loaded.edges(), world.edges());
+        assert_eq!(loaded.gravity(), world.gravity());
+        assert!(loaded.newtonian_gravity());
+        assert_eq!(loaded.particles().len(), 2);
+
+        for (before, after) in world.particles().iter().zip(loaded.particles()) {
+            assert_eq!(after.variant(), before.variant());
+            assert_eq!((after.x, after.y), (before.x, before.y));
+            assert_eq!((after.vx, after.vy), (before.vx, before.vy));
+            assert_eq!(after.temperature, before.temperature);
+            assert_eq!(after.life, before.life);
+            assert_eq!(after.burning, before.burning);
+            assert_eq!(after.compression, before.compression);
+            assert_eq!(after.color, before.color);
+        }
+    }
+
+    #[test]
+    fn read_rejects_other_files() {
+        assert!(read(&mut &b"OPS1 not a world"[..]).is_err());
+    }
+}
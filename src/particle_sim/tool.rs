@@ -0,0 +1,87 @@
+//! Tools for editing the world, working out which cells an edit covers.
+
+use std::collections::{HashSet, VecDeque};
+
+use line_drawing::Bresenham;
+
+use super::{occupancy::NEIGHBOURS, world::World};
+
+/// Most cells a single flood fill can cover, so filling the open world doesn't stall.
+const MAX_FILL: usize = 40_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tool {
+    /// Paint with the brush wherever the mouse is held.
+    Brush,
+    /// Drag to stamp the brush along a straight line.
+    Line,
+    /// Drag to fill a rectangle.
+    Rectangle,
+    /// Click to fill the enclosed empty region, or erase the connected particles of an element.
+    Fill,
+}
+
+impl Tool {
+    /// Switch to the next tool, wrapping around after the last.
+    pub fn next(self) -> Self {
+        match self {
+            Tool::Brush => Tool::Line,
+            Tool::Line => Tool::Rectangle,
+            Tool::Rectangle => Tool::Fill,
+            Tool::Fill => Tool::Brush,
+        }
+    }
+}
+
+/// The cells on a straight line between two cells, both ends included.
+pub fn line(from: (i32, i32), to: (i32, i32)) -> Vec<(i32, i32)> {
+    Bresenham::new(from, to).collect()
+}
+
+/// The cells of the filled rectangle with two cells as opposite corners.
+pub fn rectangle(from: (i32, i32), to: (i32, i32)) -> Vec<(i32, i32)> {
+    let (left, right) = (from.0.min(to.0), from.0.max(to.0));
+    let (top, bottom) = (from.1.min(to.1), from.1.max(to.1));
+
+    (top..=bottom)
+        .flat_map(|y| (left..=right).map(move |x| (x, y)))
+        .collect()
+}
+
+/// The region of the world connected to a cell, through neighbours that hold the same element as
+/// it, or are empty if it's empty.
+///
+/// The fill stops at the edges of the world, and after `MAX_FILL` cells.
+pub fn flood_fill(world: &World, start: (i32, i32)) -> Vec<(i32, i32)> {
+    let (width, height) = (world.width() as i32, world.height() as i32);
+    let contents = |(x, y): (i32, i32)| world.particle_at(x, y).map(|particle| particle.variant());
+
+    let inside = |(x, y): (i32, i32)| x >= 0 && y >= 0 && x < width && y < height;
+
+    if !inside(start) {
+        return Vec::new();
+    }
+
+    let target = contents(start);
+    let mut seen = HashSet::from([start]);
+    let mut queue = VecDeque::from([start]);
+    let mut cells = Vec::new();
+
+    while let Some((x, y)) = queue.pop_front() {
+        cells.push((x, y));
+
+        if cells.len() >= MAX_FILL {
+            break;
+        }
+
+        for (dx, dy) in NEIGHBOURS {
+            let cell = (x + dx, y + dy);
+
+            if inside(cell) && contents(cell) == target && seen.insert(cell) {
+                queue.push_back(cell);
+            }
+        }
+    }
+
+    cells
+}
@@ -1,140 +1,286 @@
+use crate::particle_sim::particle::{cell_centre, random_direction, PhysicsType};
 pub use crate::particle_sim::particle::{Particle, ParticleVariant};
-use rand::{rngs::ThreadRng, Rng};
+use rand::Rng;
 use rapier2d::prelude::*;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::Path;
 
 use super::{
+    air::AirGrid,
+    backend::Backend,
+    clock::DEFAULT_TICK_RATE,
+    combustion::burn,
+    edge::{EdgeMode, Edges},
+    element::PROP_BREAKABLE,
+    explosive::detonate,
+    fluid::flow,
+    fusion::fuse,
     geometry::Point,
     graphics::{blit, Sprite},
+    gravity::{Gravity, GravityField},
+    heat::conduct_heat,
     loader::load_assets,
+    occupancy::Occupancy,
+    ops::{self, ImportReport},
+    phase::transition_all,
+    radioactivity::irradiate,
+    rapier::RapierBackend,
+    reaction::{age, Reaction},
+    save,
 };
 
+/// Impulse applied to a dynamic particle per unit of air velocity around it, each step.
+const AIR_PUSH: f32 = 0.05;
+
+/// Size of a world created with `World::new`, in cells.
+pub const DEFAULT_WIDTH: usize = 200;
+pub const DEFAULT_HEIGHT: usize = 200;
+/// Largest width or height of a world, in cells, so a mistyped size can't exhaust memory.
+pub const MAX_SIZE: usize = 8192;
+
 pub struct World {
     y: i32,
+    width: usize,
+    height: usize,
     particle_sprite: Sprite,
-    particles: Vec<Particle>,
-    pub rigid_body_set: RigidBodySet,
-    collider_set: ColliderSet,
-    integration_parameters: IntegrationParameters,
-    physics_pipeline: PhysicsPipeline,
-    island_manager: IslandManager,
-    broad_phase: BroadPhase,
-    narrow_phase: NarrowPhase,
-    impulse_joint_set: ImpulseJointSet,
-    multibody_joint_set: MultibodyJointSet,
-    ccd_solver: CCDSolver,
-    physics_hooks: (),
-    event_handler: (),
+    backend: Box<dyn Backend>,
+    /// What happens to particles that leave the world through each edge.
+    edges: Edges,
+    gravity: Gravity,
+    /// Whether heavy particles pull on everything around them, on top of `gravity`.
+    newtonian_gravity: bool,
+    /// The acceleration due to gravity across the world, recomputed every step.
+    gravity_field: GravityField,
+    /// Simulated time per step, in seconds.
+    time_step: f32,
+    /// Which particle is in each cell, kept in sync as particles move, appear and disappear.
+    occupancy: Occupancy,
+    air: AirGrid,
 }
 
 impl World {
+    /// Add a particle at the centre of the cell containing `(x, y)`, returning whether there was
+    /// room for it.
+    ///
+    /// Particles can't be stacked or placed outside the world, so this fails if the cell is
+    /// already occupied or out of bounds.
     pub fn add_particle(&mut self, x: f32, y: f32, variant: ParticleVariant) -> bool {
-        let mut rng: ThreadRng = rand::thread_rng();
+        let mut rng = rand::thread_rng();
+        let colour = variant.element().random_colour(&mut rng);
+        let (x, y) = cell_centre((x.floor() as i32, y.floor() as i32));
 
-        self.particles.push(Particle::new(
-            x,
-            y,
-            variant,
-            crate::COLORS[rng.gen_range(0..7)],
-            &mut self.collider_set,
-            &mut self.rigid_body_set,
-        ));
+        self.insert_particle(Particle::new(x, y, variant, colour))
+    }
+
+    /// Add an already made particle, such as one imported from another game, keeping all of its
+    /// state.
+    ///
+    /// Fails in the same cases as `add_particle`.
+    pub fn insert_particle(&mut self, particle: Particle) -> bool {
+        let (cx, cy) = particle.cell();
+
+        if !self.occupancy.contains(cx, cy)
+            || (particle.element().physics_type != PhysicsType::ENERGY
+                && self.occupancy.is_occupied(cx, cy))
+        {
+            return false;
+        }
+
+        if !self.backend.add(particle) {
+            return false;
+        }
+
+        let index = self.backend.particles().len() - 1;
+        self.occupancy
+            .insert(index, &self.backend.particles()[index]);
 
         true
     }
 
+    /// Put back particles saved from a world of the same size, keeping all of their state.
+    ///
+    /// Unlike `insert_particle` this doesn't refuse particles in occupied cells, since bodies
+    /// in the middle of settling can briefly share one.
+    pub fn restore_particles(&mut self, particles: impl IntoIterator<Item = Particle>) {
+        for particle in particles {
+            self.backend.add(particle);
+        }
+
+        self.occupancy.rebuild(self.backend.particles());
+    }
+
+    /// Remove the particle at `index`, moving the last particle into its place.
+    ///
+    /// Everything the backend keeps for the particle, such as its rapier body and collider, is
+    /// freed along with it.
+    pub fn remove_particle(&mut self, index: usize) -> Particle {
+        let last = self.backend.particles().len() - 1;
+        let particle = self.backend.remove(index);
+
+        self.occupancy.remove(index, &particle);
+
+        if index != last {
+            self.occupancy
+                .remove(last, &self.backend.particles()[index]);
+            self.occupancy
+                .insert(index, &self.backend.particles()[index]);
+        }
+
+        particle
+    }
+
+    /// Remove the particle in the given cell, returning whether there was one.
+    pub fn remove_particle_at(&mut self, x: i32, y: i32) -> bool {
+        match self.occupancy.get(x, y) {
+            Some(index) => {
+                self.remove_particle(index);
+                true
+            }
+            None => false,
+        }
+    }
+
     pub fn clear_particles(&mut self) -> bool {
-        self.particles.clear();
+        // Remove from the end so nothing has to be moved into the freed slots
+        while let Some(last) = self.backend.particles().len().checked_sub(1) {
+            self.remove_particle(last);
+        }
 
         true
     }
 
-    pub fn new() -> Self {
-        let assets = load_assets();
+    /// How many steps make up a second of simulated time.
+    pub fn tick_rate(&self) -> f32 {
+        1.0 / self.time_step
+    }
 
-        let mut new_particles: Vec<Particle> = Vec::new();
-        let mut rng = rand::thread_rng();
+    /// Set how many steps make up a second of simulated time.
+    pub fn set_tick_rate(&mut self, ticks_per_second: f32) {
+        self.time_step = 1.0 / ticks_per_second;
+    }
 
-        let mut broad_phase: BroadPhase = BroadPhase::new();
-        let mut ccd_solver: CCDSolver = CCDSolver::new();
-        let mut collider_set: ColliderSet = ColliderSet::new();
-        let mut impulse_joint_set: ImpulseJointSet = ImpulseJointSet::new();
-        let mut island_manager: IslandManager = IslandManager::new();
-        let mut multibody_joint_set: MultibodyJointSet = MultibodyJointSet::new();
-        let mut narrow_phase: NarrowPhase = NarrowPhase::new();
-        let mut physics_pipeline: PhysicsPipeline = PhysicsPipeline::new();
-        let mut rigid_body_set: RigidBodySet = RigidBodySet::new();
+    pub fn edges(&self) -> Edges {
+        self.edges
+    }
 
-        let ground_collider = ColliderBuilder::cuboid(crate::WIDTH as f32, 0.1)
-            .translation(vector![0.0, crate::HEIGHT as f32])
-            .build();
+    /// Change what happens at the edges of the world, taking effect from the next step.
+    pub fn set_edges(&mut self, edges: Edges) {
+        self.edges = edges;
+        self.backend.set_edges(edges);
+    }
 
-        let left_wall_collider: Collider = ColliderBuilder::cuboid(0.1, crate::HEIGHT as f32)
-            .translation(vector![0.0, 0.0])
-            .build();
+    pub fn gravity(&self) -> Gravity {
+        self.gravity
+    }
 
-        let right_wall_collider: Collider = ColliderBuilder::cuboid(0.1, crate::HEIGHT as f32)
-            .translation(vector![crate::WIDTH as f32, 0.0])
-            .build();
+    /// Change the gravity mode, taking effect from the next step.
+    pub fn set_gravity(&mut self, gravity: Gravity) {
+        self.gravity = gravity;
+    }
 
-        collider_set.insert(ground_collider);
-        collider_set.insert(left_wall_collider);
-        collider_set.insert(right_wall_collider);
+    pub fn newtonian_gravity(&self) -> bool {
+        self.newtonian_gravity
+    }
 
-        let particle_sprite = Sprite::new(&assets, crate::particle_sim::graphics::Frame::Particle);
+    /// Turn Newtonian gravity from heavy elements on or off, taking effect from the next step.
+    pub fn set_newtonian_gravity(&mut self, enabled: bool) {
+        self.newtonian_gravity = enabled;
+    }
 
-        let mut integration_parameters = IntegrationParameters::default();
+    pub fn particles(&self) -> &[Particle] {
+        self.backend.particles()
+    }
 
-        integration_parameters.dt = 1.0 / 45.0;
+    /// Name of the backend moving the particles, as accepted by `backend::from_name`.
+    pub fn backend_name(&self) -> &'static str {
+        self.backend.name()
+    }
 
-        /*let (collision_send, collision_recv) = crossbeam::channel::unbounded();
-        let (contact_force_send, contact_force_recv) = crossbeam::channel::unbounded();
-        let event_handler = ChannelEventCollector::new(collision_send, contact_force_send);*/
+    /// The particle in the given cell, if any.
+    pub fn particle_at(&self, x: i32, y: i32) -> Option<&Particle> {
+        self.occupancy
+            .get(x, y)
+            .map(|i| &self.backend.particles()[i])
+    }
+
+    /// Width of the world, in cells.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Height of the world, in cells.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn new() -> Self {
+        Self::with_backend(Box::new(RapierBackend::new(DEFAULT_WIDTH, DEFAULT_HEIGHT)))
+    }
+
+    /// Create a world whose particles are moved by the given backend, the size the backend was
+    /// created for.
+    pub fn with_backend(backend: Box<dyn Backend>) -> Self {
+        let mut rng = rand::thread_rng();
+        let mut world = Self::empty(backend);
+        let (width, height) = (world.width, world.height);
 
         for _n in 1..20 {
-            new_particles.push(Particle::new(
-                (rng.gen_range(4..crate::WIDTH - 4)) as f32,
-                (rng.gen_range(4..crate::HEIGHT - 4)) as f32,
+            world.add_particle(
+                (rng.gen_range(4..width - 4)) as f32,
+                (rng.gen_range(4..height - 4)) as f32,
                 ParticleVariant::URAN,
-                crate::COLORS[rng.gen_range(0..7)],
-                &mut collider_set,
-                &mut rigid_body_set,
-            ));
+            );
         }
+
+        world
+    }
+
+    /// Create a world like `with_backend`, but without any particles in it.
+    pub fn empty(backend: Box<dyn Backend>) -> Self {
+        let (width, height) = backend.size();
+        let assets = load_assets();
+
+        let particle_sprite = Sprite::new(&assets, crate::particle_sim::graphics::Frame::Particle);
+
         Self {
             y: 0,
+            width,
+            height,
             particle_sprite,
-            particles: new_particles.to_vec(),
-            rigid_body_set,
-            collider_set,
-            integration_parameters,
-            physics_pipeline,
-            island_manager,
-            broad_phase,
-            narrow_phase,
-            impulse_joint_set,
-            multibody_joint_set,
-            ccd_solver,
-            physics_hooks: (),
-            event_handler: (),
+            backend,
+            edges: Edges::default(),
+            gravity: Gravity::default(),
+            newtonian_gravity: false,
+            gravity_field: GravityField::new(width, height),
+            time_step: 1.0 / DEFAULT_TICK_RATE,
+            occupancy: Occupancy::new(width, height),
+            air: AirGrid::new(width, height),
         }
     }
 
     pub fn draw(&self, frame: &mut [u8]) {
         // Draw the particles
-        for particle in &self.particles {
-            let x = particle.x as usize;
-            let y = particle.y as usize;
+        for particle in self.backend.particles() {
+            // Drawn in the same cell it's indexed in
+            let (x, y) = particle.cell();
+
+            if x < 0 || y < 0 {
+                continue;
+            }
+
             //let r = particle.radius as u32;
 
             let color = particle.color;
-            let pos = Point::new(x, y);
+            let pos = Point::new(x as usize, y as usize);
 
-            blit(frame, &pos, &self.particle_sprite, color);
+            blit(frame, self.width, &pos, &self.particle_sprite, color);
 
             /*for i in x..=x {
                 for j in y..=y {
-                    if i < crate::WIDTH as u32 && j < crate::HEIGHT as u32 {
-                        let offset = (j * crate::WIDTH as u32 + i) as usize * 4;
+                    if i < self.width && j < self.height {
+                        let offset = (j * self.width + i) * 4;
 
                         frame[offset] = (color[0]) as u8;
                         frame[offset + 1] = (color[1]) as u8;
@@ -150,7 +296,7 @@ impl World {
         if self.y == 200 {
             self.y = 0;
         } else {
-            self.y = self.y + 1;
+            self.y += 1;
         }
 
         /*if (self.y % 2 == 0) {
@@ -161,23 +307,295 @@ impl World {
             );
         }*/
 
-        self.physics_pipeline.step(
-            &vector![0.0, 9.81],
-            &self.integration_parameters,
-            &mut self.island_manager,
-            &mut self.broad_phase,
-            &mut self.narrow_phase,
-            &mut self.rigid_body_set,
-            &mut self.collider_set,
-            &mut self.impulse_joint_set,
-            &mut self.multibody_joint_set,
-            &mut self.ccd_solver,
-            &self.physics_hooks,
-            &self.event_handler,
+        self.gravity_field.update(
+            self.gravity,
+            self.newtonian_gravity,
+            self.backend.particles(),
+        );
+        self.backend.step(self.time_step, &self.gravity_field);
+        self.apply_edges();
+        self.occupancy.rebuild(self.backend.particles());
+
+        conduct_heat(self.backend.particles_mut(), &self.occupancy, &mut self.air);
+        self.air.update_heat();
+
+        for particle in self.backend.particles() {
+            if let Some(velocity) = particle.element().fan {
+                self.air.set_velocity(particle.x, particle.y, velocity);
+            }
+        }
+
+        self.air.update_flow();
+        self.blow();
+
+        let mut reactions = Vec::new();
+
+        transition_all(self.backend.particles(), &mut reactions);
+        burn(
+            self.backend.particles_mut(),
+            &self.occupancy,
+            &mut reactions,
+        );
+        detonate(self.backend.particles(), &self.occupancy, &mut reactions);
+        irradiate(
+            self.backend.particles_mut(),
+            &self.occupancy,
+            &mut self.air,
+            &mut reactions,
+        );
+        fuse(
+            self.backend.particles_mut(),
+            &self.occupancy,
+            &self.air,
+            &mut reactions,
+        );
+
+        // Some backends' own rules already layer and spread fluids
+        if !self.backend.moves_fluids() {
+            flow(
+                self.backend.particles(),
+                &self.occupancy,
+                &self.gravity_field,
+                &mut reactions,
+            );
+        }
+
+        age(self.backend.particles_mut(), &mut reactions);
+
+        self.apply_reactions(reactions);
+    }
+
+    /// Delete or wrap around the particles that ended up outside the world during a step.
+    ///
+    /// Particles slipping past a solid edge are put back inside, except energy, which is
+    /// absorbed by the wall.
+    fn apply_edges(&mut self) {
+        let (width, height) = (self.width as f32, self.height as f32);
+        let mut removed = Vec::new();
+
+        for i in 0..self.backend.particles().len() {
+            let particle = &self.backend.particles()[i];
+            let (x, y) = (particle.x, particle.y);
+            let beyond = self.edges.beyond(x, y, width, height);
+
+            if beyond == [None, None] {
+                continue;
+            }
+
+            let energy = particle.element().physics_type == PhysicsType::ENERGY;
+
+            if beyond.contains(&Some(EdgeMode::Void))
+                || (energy && beyond.contains(&Some(EdgeMode::Solid)))
+            {
+                removed.push(i);
+                continue;
+            }
+
+            let wrap = |position: f32, size: f32, mode: Option<EdgeMode>| match mode {
+                Some(EdgeMode::Wrap) => position.rem_euclid(size),
+                Some(_) => position.clamp(0.5, size - 0.5),
+                None => position,
+            };
+
+            self.backend
+                .set_position(i, wrap(x, width, beyond[0]), wrap(y, height, beyond[1]));
+        }
+
+        // Remove from the end so the remaining indices stay valid
+        for i in removed.into_iter().rev() {
+            self.remove_particle(i);
+        }
+    }
+
+    /// Write the world to a file in the format described in `save`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        save::write(self, &mut writer)?;
+        writer.flush()
+    }
+
+    /// Read a world saved with `save`.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        save::read(&mut BufReader::new(File::open(path)?))
+    }
+
+    /// Import a save or stamp from the original Powder Toy, with the backend of the given name.
+    ///
+    /// The world takes the save's size. Elements we don't have are left out and listed in the
+    /// report.
+    pub fn import<P: AsRef<Path>>(path: P, backend_name: &str) -> io::Result<(Self, ImportReport)> {
+        ops::read(&mut BufReader::new(File::open(path)?), backend_name)
+    }
+
+    /// Push dynamic particles along with the air around them.
+    ///
+    /// The impulse doesn't depend on mass, so dense particles are harder to move.
+    fn blow(&mut self) {
+        for i in 0..self.backend.particles().len() {
+            let particle = &self.backend.particles()[i];
+
+            if !particle.element().physics_type.is_dynamic() {
+                continue;
+            }
+
+            let (vx, vy) = self.air.velocity_at(particle.x, particle.y);
+
+            if vx != 0.0 || vy != 0.0 {
+                self.backend.apply_impulse(i, vector![vx, vy] * AIR_PUSH);
+            }
+        }
+    }
+
+    /// Apply the reactions collected during a step.
+    ///
+    /// Explosions are resolved first since they can destroy other particles. Removals win over
+    /// changes to the same particle, and are applied last so the indices used by the other
+    /// reactions stay valid.
+    fn apply_reactions(&mut self, mut reactions: Vec<Reaction>) {
+        let blasts: Vec<usize> = reactions
+            .iter()
+            .filter_map(|reaction| match reaction {
+                Reaction::Explode(i) => Some(*i),
+                _ => None,
+            })
+            .collect();
+
+        for i in blasts {
+            self.explode(i, &mut reactions);
+        }
+
+        let removed: HashSet<usize> = reactions
+            .iter()
+            .filter_map(|reaction| match reaction {
+                Reaction::Remove(i) => Some(*i),
+                _ => None,
+            })
+            .collect();
+
+        for reaction in reactions {
+            match reaction {
+                Reaction::Change(i, variant) if !removed.contains(&i) => {
+                    self.change_variant(i, variant)
+                }
+                Reaction::Spawn(x, y, variant) => {
+                    self.add_particle(x, y, variant);
+                }
+                Reaction::Scatter(i) if !removed.contains(&i) => {
+                    let particle = &self.backend.particles()[i];
+                    let speed = vector![particle.vx, particle.vy].norm();
+
+                    self.backend.set_velocity(i, random_direction() * speed);
+                }
+                Reaction::Push(i, vx, vy) if !removed.contains(&i) => {
+                    let particle = &self.backend.particles()[i];
+                    let velocity = vector![particle.vx + vx, particle.vy + vy];
+
+                    self.backend.set_velocity(i, velocity);
+                }
+                Reaction::Swap(i, j) if !removed.contains(&i) && !removed.contains(&j) => {
+                    self.backend.swap(i, j);
+                    self.occupancy.swap(i, j, self.backend.particles());
+                }
+                _ => {}
+            }
+        }
+
+        let mut removed: Vec<usize> = removed.into_iter().collect();
+        removed.sort_unstable_by(|a, b| b.cmp(a));
+
+        for i in removed {
+            self.remove_particle(i);
+        }
+    }
+
+    /// Detonate the explosive particle at `index`, turning it into fire.
+    ///
+    /// Everything within the blast radius is heated and dynamic bodies are pushed away from the
+    /// centre, both falling off linearly with distance. Breakable particles in the radius are
+    /// destroyed and the blast raises the pressure of the air around it.
+    fn explode(&mut self, index: usize, reactions: &mut Vec<Reaction>) {
+        let explosion = match &self.backend.particles()[index].element().explosion {
+            Some(explosion) => explosion,
+            None => return,
+        };
+
+        let (x, y) = {
+            let particle = &self.backend.particles()[index];
+            (particle.x, particle.y)
+        };
+        let (cx, cy) = self.backend.particles()[index].cell();
+        let reach = explosion.radius.ceil() as i32;
+
+        for dy in -reach..=reach {
+            for dx in -reach..=reach {
+                let j = match self.occupancy.get(cx + dx, cy + dy) {
+                    Some(j) if j != index => j,
+                    _ => continue,
+                };
+
+                let particle = &mut self.backend.particles_mut()[j];
+                let offset = vector![particle.x - x, particle.y - y];
+                let distance = offset.norm();
+
+                if distance > explosion.radius {
+                    continue;
+                }
+
+                let falloff = 1.0 - distance / explosion.radius;
+                particle.temperature += explosion.heat * falloff;
+
+                if particle.element().has_flag(PROP_BREAKABLE) {
+                    reactions.push(Reaction::Remove(j));
+                } else if particle.element().physics_type.is_dynamic() {
+                    let direction = if distance > 0.0 {
+                        offset / distance
+                    } else {
+                        vector![0.0, -1.0]
+                    };
+
+                    self.backend
+                        .apply_impulse(j, direction * explosion.impulse * falloff);
+                }
+            }
+        }
+
+        self.backend.particles_mut()[index].temperature += explosion.heat;
+        self.air.add_pressure(x, y, explosion.pressure);
+        reactions.push(Reaction::Change(index, ParticleVariant::FIRE));
+    }
+
+    /// Turn a particle into another element in place, keeping its position, velocity and
+    /// temperature.
+    fn change_variant(&mut self, index: usize, variant: ParticleVariant) {
+        let mut rng = rand::thread_rng();
+        let particle = &self.backend.particles()[index];
+
+        let mut replacement = Particle::new(
+            particle.x,
+            particle.y,
+            variant,
+            variant.element().random_colour(&mut rng),
         );
 
-        for particle in &mut self.particles {
-            particle.update(&mut self.rigid_body_set);
+        replacement.temperature = particle.temperature;
+
+        // Energy keeps the random velocity it was created with
+        if variant.element().physics_type != PhysicsType::ENERGY {
+            replacement.vx = particle.vx;
+            replacement.vy = particle.vy;
         }
+
+        self.occupancy
+            .remove(index, &self.backend.particles()[index]);
+        self.backend.replace(index, replacement);
+        self.occupancy
+            .insert(index, &self.backend.particles()[index]);
+    }
+}
+
+impl Default for World {
+    fn default() -> Self {
+        Self::new()
     }
 }